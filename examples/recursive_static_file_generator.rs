@@ -17,29 +17,27 @@ fn main() {
         dataset_path
     ).unwrap();
 
-    let (value_min, value_max) = (0., 20.);
+    // pick the colorbar range from the dataset values (2% - 98% percentiles)
+    let statistics = dataset.statistics(None).unwrap();
+    println!("Values range from {} to {}", &statistics.min, &statistics.max);
     println!("Creating a BrBG renderer");
     let renderer = tiler::Renderer::from_dataset(
         dataset,        // input dataset
-        tiler::Scale::Linear { // Use a linear range of color
-            min: value_min, // minimum value of the colorbar
-            max: value_max  // maximum value of the colorbar 
-        },
+        tiler::Scale::from_statistics(&statistics),
         tiler::ColorMap::BrBG   // Brown to Green
-    ).unwrap();
+    ).unwrap()
+        .with_threads(4);  // render the tiles on 4 threads
 
     let tile = tiler::Tile {x: 0, y: 0, z: 0 };
 
-    // recursively creates images until zoom 6
-    if let Ok(tile_imgs) = renderer.render_n_level_tile(&tile, 6) {
-        for tile_img in tile_imgs {
-            let tile_dir = format!("{}/{}/{}", &cache_path, &tile_img.z, &tile_img.x);
-            if let Ok(_) =  create_dir_all(&tile_dir) {
-                let tile_path = format!("{}/{}.png", &tile_dir, &tile_img.y);
-                tile_img.save(&tile_path);
-            }
+    // recursively creates images until zoom 6, saving each one as soon as it is rendered
+    renderer.render_n_level_tile_with(&tile, 6, 1, |tile_img| {
+        let tile_dir = format!("{}/{}/{}", &cache_path, &tile_img.z, &tile_img.x);
+        if let Ok(_) =  create_dir_all(&tile_dir) {
+            let tile_path = format!("{}/{}.png", &tile_dir, &tile_img.y);
+            tile_img.save(&tile_path);
         }
-    }
+    }).unwrap();
     println!("You show see the result by opening ./examples_data/viewer.html with your browser.");
 }
 
@@ -0,0 +1,84 @@
+use std::f32;
+use std::cmp::Ordering;
+
+/// Defines how the cells covered by a single pixel are combined,
+/// when the data is denser than the pixels (ie: at low zoom levels).
+///
+/// Missing (NaN) cells are ignored, the pixel is missing if every cell is.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Aggregation {
+    /// Arithmetic mean
+    #[default]
+    Mean,
+    /// Lowest value
+    Min,
+    /// Highest value, keeps the peaks (eg: storms) visible
+    Max,
+    /// Median value
+    Median,
+    /// Most frequent value (the lowest one in case of tie), suited for categorical data
+    Mode,
+    /// First valid value, starting from the south west cell
+    FirstValid,
+    /// Mean weighted by the area of each cell overlapping the pixel (on the sphere),
+    /// conserves the integral of flux fields (eg: precipitation totals) across zooms.
+    ///
+    /// The areas are only known when regridding a `TileData` with `Resampling::Auto`
+    /// (and more cells than pixels), elsewhere it behaves as `Mean`.
+    Conservative,
+}
+
+impl Aggregation {
+    /// Combines `values` into a single one.
+    ///
+    /// The cells areas being unknown, `Conservative` falls back to the (unweighted) mean.
+    pub fn aggregate(&self, values: &mut Vec<f32>) -> f32 {
+        values.retain(|v| !v.is_nan());
+        if values.is_empty() {
+            return f32::NAN;
+        }
+        match *self {
+            Aggregation::Mean | Aggregation::Conservative => values.iter().sum::<f32>() / values.len() as f32,
+            Aggregation::Min => values.iter().cloned().fold(f32::INFINITY, f32::min),
+            Aggregation::Max => values.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+            Aggregation::FirstValid => values[0],
+            Aggregation::Median => {
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let n = values.len();
+                if n % 2 == 1 {
+                    values[n / 2]
+                } else {
+                    (values[n / 2 - 1] + values[n / 2]) / 2.
+                }
+            }
+            Aggregation::Mode => {
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let (mut mode, mut mode_count) = (values[0], 0);
+                let mut start = 0;
+                for i in 1..(values.len() + 1) {
+                    if i == values.len() || values[i] != values[start] {
+                        if i - start > mode_count {
+                            mode = values[start];
+                            mode_count = i - start;
+                        }
+                        start = i;
+                    }
+                }
+                mode
+            }
+        }
+    }
+}
+
+#[test]
+fn test_aggregations() {
+    let values = vec![3., f32::NAN, 1., 3., 7.];
+    let aggregate = |aggregation: Aggregation| aggregation.aggregate(&mut values.clone());
+    assert_eq!(aggregate(Aggregation::Mean), 3.5);
+    assert_eq!(aggregate(Aggregation::Min), 1.);
+    assert_eq!(aggregate(Aggregation::Max), 7.);
+    assert_eq!(aggregate(Aggregation::Median), 3.);
+    assert_eq!(aggregate(Aggregation::Mode), 3.);
+    assert_eq!(aggregate(Aggregation::FirstValid), 3.);
+    assert!(Aggregation::Max.aggregate(&mut vec![f32::NAN]).is_nan());
+}
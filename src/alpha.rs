@@ -0,0 +1,110 @@
+use std::f32;
+use source::DataSource;
+use scale::{Scale, normalize};
+use tile::Tile;
+use tiledata::TileGrid;
+use resampling::Resampling;
+use aggregation::Aggregation;
+#[cfg(test)]
+use tiledata::TILE_SIZE;
+
+/// Defines the opacity of the rendered pixels,
+/// it is multiplied with the alpha channel of the color map.
+pub enum AlphaRamp {
+    /// Opacity grows linearly from transparent to opaque as the normalized value
+    /// goes from `start` to `end` (both in [0; 1]),
+    /// eg: light rain fades out so the basemap shows through.
+    Value { start: f32, end: f32 },
+    /// Opacity given by a second variable (eg: a probability), normalized with its own scale.
+    /// Pixels where it is missing are transparent.
+    Source {
+        source: Box<dyn DataSource>,
+        scale: Scale,
+    },
+}
+
+/// Returns the linear ramp from 0 (at `start`) to 1 (at `end`)
+fn ramp(value: f32, start: f32, end: f32) -> f32 {
+    if end <= start {
+        return if value >= end { 1. } else { 0. };
+    }
+    ((value - start) / (end - start)).clamp(0., 1.)
+}
+
+impl AlphaRamp {
+    /// Multiplies the alpha channel of the pixels of a tile (as built by the `Renderer`)
+    /// with the opacity of the ramp.
+    ///
+    /// #Args
+    ///  * `values` the tile grid (see `TileData::to_tile_grid`)
+    ///  * `scale` the scale used to normalize `values`
+    pub fn apply(
+        &self,
+        tile: &Tile,
+        values: &TileGrid,
+        scale: &Scale,
+        pixels: &mut [u8],
+    ) {
+        let opacities = match *self {
+            AlphaRamp::Value { start, end } => {
+                let mut opacities = values.clone();
+                for opacity in opacities.values.iter_mut() {
+                    *opacity = ramp(normalize(scale, *opacity), start, end);
+                }
+                opacities
+            }
+            AlphaRamp::Source { ref source, ref scale } => {
+                let mut opacities = match source.sized_tile_data(tile, 0, values.size) {
+                    Ok(tile_data) => tile_data.to_tile_grid_with(
+                        values.size,
+                        &Resampling::Auto,
+                        &Aggregation::Mean,
+                        None,
+                    ),
+                    Err(_) => TileGrid::new(values.size),
+                };
+                for value in opacities.values.iter_mut() {
+                    *value = if value.is_nan() {
+                        0.
+                    } else {
+                        normalize(scale, *value).clamp(0., 1.)
+                    };
+                }
+                opacities
+            }
+        };
+        // pixels rows go from north to south
+        let size = opacities.size;
+        for (i_lat, row) in opacities.rows().enumerate() {
+            let offset = 4 * size * (size - 1 - i_lat);
+            for (i_lon, opacity) in row.iter().enumerate() {
+                let alpha = &mut pixels[offset + 4 * i_lon + 3];
+                *alpha = (*alpha as f32 * opacity).round() as u8;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_value_alpha_ramp() {
+    let mut values = TileGrid { size: TILE_SIZE, values: vec![0.; TILE_SIZE * TILE_SIZE] };
+    values[0][0] = 10.;
+    values[0][1] = 5.;
+    let mut pixels = vec![255u8; 4 * TILE_SIZE * TILE_SIZE];
+    let ramp = AlphaRamp::Value { start: 0., end: 0.5 };
+    ramp.apply(
+        &Tile { x: 0, y: 0, z: 0 },
+        &values,
+        &Scale::Linear { min: 0., max: 10. },
+        &mut pixels,
+    );
+    // the first row of values is the last row of pixels
+    let offset = 4 * TILE_SIZE * (TILE_SIZE - 1);
+    assert_eq!(pixels[offset + 3], 255);
+    assert_eq!(pixels[offset + 7], 255);
+    assert_eq!(pixels[offset + 11], 0);
+    values[0][1] = 1.;
+    let mut pixels = vec![255u8; 4 * TILE_SIZE * TILE_SIZE];
+    ramp.apply(&Tile { x: 0, y: 0, z: 0 }, &values, &Scale::Linear { min: 0., max: 10. }, &mut pixels);
+    assert_eq!(pixels[offset + 7], 51);
+}
@@ -0,0 +1,301 @@
+use colormap::{ColorMap, rgba};
+use source::DataSource;
+use tiledata::TileGrid;
+use tile::Tile;
+use gapfill::GapFill;
+use filter::Filter;
+use mask::Mask;
+use alpha::AlphaRamp;
+use resampling::Resampling;
+use aggregation::Aggregation;
+use renderer::ImgTile;
+use pipeline::{Pipeline, check_tile_size};
+use scale::{Scale, normalize};
+
+/// A 2-D colour table, mapping a pair of normalized values (both in [0; 1]) to a color.
+///
+/// The first value selects a column, the second one a row.
+pub struct BivariateColorTable {
+    columns: usize,
+    rows: usize,
+    /// Flattened (row, column) colors
+    colors: Vec<[u8; 4]>,
+}
+
+impl BivariateColorTable {
+    /// Creates a table from its flattened (row, column) colors
+    pub fn new(columns: usize, rows: usize, colors: Vec<[u8; 4]>) -> Result<Self, String> {
+        if columns == 0 || rows == 0 || colors.len() != columns * rows {
+            return Err("The number of colors does not match the table size".into());
+        }
+        Ok(Self { columns, rows, colors })
+    }
+
+    /// Creates a table by bilinear interpolation of the colors of its 4 corners,
+    /// eg: the usual 3 x 3 bivariate choropleth palettes.
+    pub fn from_corners(
+        columns: usize,
+        rows: usize,
+        corners: [[u8; 4]; 4],
+    ) -> Result<Self, String> {
+        let [low_low, high_low, low_high, high_high] = corners;
+        let mut colors: Vec<[u8; 4]> = Vec::with_capacity(columns * rows);
+        let step = |n: usize, i: usize| if n > 1 { i as f32 / (n - 1) as f32 } else { 0. };
+        for row in 0..rows {
+            let v = step(rows, row);
+            for column in 0..columns {
+                let u = step(columns, column);
+                let mut color = [0u8; 4];
+                for (c, value) in color.iter_mut().enumerate() {
+                    let low = low_low[c] as f32 * (1. - u) + high_low[c] as f32 * u;
+                    let high = low_high[c] as f32 * (1. - u) + high_high[c] as f32 * u;
+                    *value = (low * (1. - v) + high * v).round() as u8;
+                }
+                colors.push(color);
+            }
+        }
+        Self::new(columns, rows, colors)
+    }
+
+    /// Colors of `color_map` for the first value, desaturated (blended towards gray)
+    /// as the second value (eg: the ensemble spread) increases.
+    pub fn desaturated(color_map: &ColorMap, columns: usize, rows: usize) -> Result<Self, String> {
+        Self::from_color_map(color_map, columns, rows, |color, v| {
+            let gray = 0.299 * color[0] as f32 + 0.587 * color[1] as f32 + 0.114 * color[2] as f32;
+            let mut blended = color;
+            for value in blended.iter_mut().take(3) {
+                *value = (*value as f32 * (1. - v) + gray * v).round() as u8;
+            }
+            blended
+        })
+    }
+
+    /// Colors of `color_map` for the first value, fading out (more transparent)
+    /// as the second value (eg: the ensemble spread) increases.
+    pub fn faded(color_map: &ColorMap, columns: usize, rows: usize) -> Result<Self, String> {
+        Self::from_color_map(color_map, columns, rows, |color, v| {
+            [color[0], color[1], color[2], (color[3] as f32 * (1. - v)).round() as u8]
+        })
+    }
+
+    fn from_color_map<F: Fn([u8; 4], f32) -> [u8; 4]>(
+        color_map: &ColorMap,
+        columns: usize,
+        rows: usize,
+        blend: F,
+    ) -> Result<Self, String> {
+        let center = |n: usize, i: usize| (i as f32 + 0.5) / n as f32;
+        let mut colors: Vec<[u8; 4]> = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            // the first row keeps the colors untouched
+            let v = if rows > 1 { row as f32 / (rows - 1) as f32 } else { 0. };
+            for column in 0..columns {
+                colors.push(blend(rgba(center(columns, column), color_map), v));
+            }
+        }
+        Self::new(columns, rows, colors)
+    }
+
+    /// Returns the color of a pair of normalized values
+    #[inline]
+    pub fn lookup(&self, u: f32, v: f32) -> [u8; 4] {
+        let index = |value: f32, n: usize| ((value * n as f32).floor().max(0.) as usize).min(n - 1);
+        self.colors[index(v, self.rows) * self.columns + index(u, self.columns)]
+    }
+}
+
+/// Renders two variables together, (eg: a forecast value and its uncertainty),
+/// each of them being normalized with its own `Scale` before looking up
+/// their color in a `BivariateColorTable`.
+///
+/// Pixels where any variable is missing are transparent.
+/// Both variables are extracted, gap filled, resampled, filtered and masked like the
+/// variable of a `Renderer`, whose builders and rendering functions it mirrors.
+pub struct BivariateRenderer {
+    first: Box<dyn DataSource>,
+    first_scale: Scale,
+    second: Box<dyn DataSource>,
+    second_scale: Scale,
+    table: BivariateColorTable,
+    alpha: Option<AlphaRamp>,
+    pipeline: Pipeline,
+}
+
+impl BivariateRenderer {
+    pub fn new(
+        first: Box<dyn DataSource>,
+        first_scale: Scale,
+        second: Box<dyn DataSource>,
+        second_scale: Scale,
+        table: BivariateColorTable,
+    ) -> Self {
+        Self { first, first_scale, second, second_scale, table, alpha: None, pipeline: Pipeline::new() }
+    }
+
+    /// Fill the small gaps (NaN cells) of both variables before rendering
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.pipeline.gap_fill = Some(gap_fill);
+        self
+    }
+
+    /// Smooth the regridded values of both variables using `filter`
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.pipeline.filter = Some(filter);
+        self
+    }
+
+    /// Only render the pixels inside `mask`, others are left transparent
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.pipeline.mask = Some(mask);
+        self
+    }
+
+    /// Modulate the opacity of the pixels, `AlphaRamp::Value` ramps over the first variable
+    pub fn with_alpha(mut self, alpha: AlphaRamp) -> Self {
+        self.alpha = Some(alpha);
+        self
+    }
+
+    /// Choose how the variables are resampled into the tile pixels (default: `Resampling::Auto`)
+    pub fn with_resampling(mut self, resampling: Resampling) -> Self {
+        self.pipeline.resampling = resampling;
+        self
+    }
+
+    /// Choose how the cells covered by a pixel are combined (see `Renderer::with_aggregation`)
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.pipeline.aggregation = aggregation;
+        self
+    }
+
+    /// Set the size (in pixels) of the rendered tiles (default: TILE_SIZE),
+    /// a power of 2 up to `MAX_TILE_SIZE`
+    pub fn with_tile_size(mut self, tile_size: usize) -> Result<Self, String> {
+        self.pipeline.tile_size = check_tile_size(tile_size)?;
+        Ok(self)
+    }
+
+    /// Set the number of threads used to render tile pyramids (default: 1)
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.pipeline.threads = threads.max(1);
+        self
+    }
+
+    /// Returns a pixel value (RGBA) from a pair of values
+    #[inline]
+    pub fn values_to_rgba(&self, first: f32, second: f32) -> [u8; 4] {
+        if first.is_nan() || second.is_nan() {
+            return [0u8; 4];
+        }
+        self.table.lookup(
+            normalize(&self.first_scale, first),
+            normalize(&self.second_scale, second),
+        )
+    }
+
+    fn sources(&self) -> [&dyn DataSource; 2] {
+        [&*self.first, &*self.second]
+    }
+
+    /// Converts the grids of both variables into pixels
+    fn values_to_colors(&self, tile: &Tile, grids: &[TileGrid]) -> Vec<u8> {
+        let (first, second) = (&grids[0], &grids[1]);
+        let size = first.size;
+        let mut colors = vec![0u8; 4 * size * size];
+        let mut count: usize = 0;
+        // iter latitude in reverse, to fit the image X,Y orientation
+        for i_lat in (0..size).rev() {
+            for i_lon in 0..size {
+                let rgba = self.values_to_rgba(first[i_lat][i_lon], second[i_lat][i_lon]);
+                colors[count..count + 4].copy_from_slice(&rgba);
+                count += 4;
+            }
+        }
+        if let Some(ref alpha) = self.alpha {
+            alpha.apply(tile, first, &self.first_scale, &mut colors);
+        }
+        colors
+    }
+
+    /// Render a Tile into an ImgTile.
+    pub fn render_tile(&self, tile: &Tile) -> Result<ImgTile, String> {
+        self.render_scaled_tile(tile, 1)
+    }
+
+    /// Render a Tile into an ImgTile `scale_factor` times larger (eg: 2 for @2x retina tiles).
+    pub fn render_scaled_tile(&self, tile: &Tile, scale_factor: usize) -> Result<ImgTile, String> {
+        self.pipeline.render_tile(&self.sources(), tile, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, grids)
+        })
+    }
+
+    /// Renders a tile and its `level` sub-levels into ImgTile,
+    /// extracting the values of both variables only once (see `Renderer::render_n_level_tile`).
+    pub fn render_n_level_tile(&self, tile: &Tile, level: u8) -> Result<Vec<ImgTile>, String> {
+        self.render_n_level_scaled_tile(tile, level, 1)
+    }
+
+    /// Renders a tile and its `level` sub-levels, like `render_n_level_tile`,
+    /// into ImgTiles `scale_factor` times larger (eg: 2 for @2x retina tiles).
+    pub fn render_n_level_scaled_tile(&self, tile: &Tile, level: u8, scale_factor: usize) -> Result<Vec<ImgTile>, String> {
+        let mut imgs: Vec<ImgTile> = Vec::new();
+        self.render_n_level_tile_with(tile, level, scale_factor, |img| imgs.push(img))?;
+        Ok(imgs)
+    }
+
+    /// Renders a tile and its `level` sub-levels, handing each ImgTile to `callback`
+    /// as soon as it is rendered (see `Renderer::render_n_level_tile_with`).
+    pub fn render_n_level_tile_with<F>(&self, tile: &Tile, level: u8, scale_factor: usize, callback: F) -> Result<(), String>
+        where F: FnMut(ImgTile) {
+        self.pipeline.render_n_level_tile_with(&self.sources(), tile, level, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, grids)
+        }, callback)
+    }
+
+    /// Renders the block of `metatile_size` x `metatile_size` tiles containing `tile`
+    /// (see `Renderer::render_metatile`).
+    pub fn render_metatile(&self, tile: &Tile, metatile_size: u32, scale_factor: usize) -> Result<Vec<ImgTile>, String> {
+        self.pipeline.render_metatile(&self.sources(), tile, metatile_size, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, grids)
+        })
+    }
+}
+
+#[test]
+fn test_bivariate_color_table() {
+    let table = BivariateColorTable::from_corners(
+        3,
+        3,
+        [[0, 0, 0, 255], [255, 0, 0, 255], [0, 0, 255, 255], [255, 0, 255, 255]],
+    ).unwrap();
+    assert_eq!(table.lookup(0., 0.), [0, 0, 0, 255]);
+    assert_eq!(table.lookup(1., 0.), [255, 0, 0, 255]);
+    assert_eq!(table.lookup(0.5, 1.), [128, 0, 255, 255]);
+
+    let table = BivariateColorTable::faded(&ColorMap::Grayscale, 4, 2).unwrap();
+    assert_eq!(table.lookup(0.9, 0.)[3], 255);
+    assert_eq!(table.lookup(0.9, 1.)[3], 0);
+    assert!(BivariateColorTable::new(2, 2, vec![[0; 4]; 3]).is_err());
+}
+
+#[test]
+fn test_bivariate_render_tile() {
+    use points::{PointData, PointInterpolation};
+    // a single point covers the whole tile with its value
+    let source = |value: f32| {
+        let source = PointData::new(&[(90., 45., value)], PointInterpolation::InverseDistance { power: 2. }, 2e7);
+        Box::new(source.unwrap())
+    };
+    let table = BivariateColorTable::from_corners(
+        3,
+        3,
+        [[0, 0, 0, 255], [255, 0, 0, 255], [0, 0, 255, 255], [255, 0, 255, 255]],
+    ).unwrap();
+    let scale = || Scale::Linear { min: 0., max: 100. };
+    let renderer = BivariateRenderer::new(source(100.), scale(), source(0.), scale(), table)
+        .with_tile_size(32).unwrap()
+        .with_threads(2);
+    let imgs = renderer.render_n_level_tile(&Tile { x: 1, y: 0, z: 1 }, 2).unwrap();
+    assert_eq!(imgs.len(), 21);
+    assert!(imgs.iter().all(|img| img.size == 32 && img.pixels[..4] == [255, 0, 0, 255]));
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Number of latitude (and longitude) indices covered by a chunk
+pub const CHUNK_SIZE: usize = 256;
+
+/// Identifies a chunk of decoded values
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkKey {
+    /// name of the variable
+    pub variable: String,
+    /// start index of every dimension of the variable,
+    /// set to 0 for the chunked (latitude and longitude) dimensions
+    pub slice: Vec<usize>,
+    /// position of the chunk along the chunked dimensions (in the variable dimension order)
+    pub chunk: (usize, usize),
+}
+
+/// A Least Recently Used cache of decoded chunks of values,
+/// which holds at most `budget` bytes of values.
+pub struct ChunkCache {
+    budget: usize,
+    used: usize,
+    // incremented on each access, to find the least recently used chunk
+    tick: u64,
+    chunks: HashMap<ChunkKey, (u64, Arc<Vec<f32>>)>,
+}
+
+impl ChunkCache {
+    /// Creates an empty cache, holding at most `budget` bytes of values
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            used: 0,
+            tick: 0,
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Returns the values of a chunk, if cached
+    pub fn get(&mut self, key: &ChunkKey) -> Option<Arc<Vec<f32>>> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.chunks.get_mut(key).map(|entry| {
+            entry.0 = tick;
+            entry.1.clone()
+        })
+    }
+
+    /// Stores the values of a chunk, evicting the least recently used chunks
+    /// until they fit in the budget (chunks larger than the budget are not stored).
+    pub fn insert(&mut self, key: ChunkKey, values: Arc<Vec<f32>>) {
+        let size = values.len() * std::mem::size_of::<f32>();
+        if size > self.budget {
+            return;
+        }
+        if let Some((_, previous)) = self.chunks.remove(&key) {
+            self.used -= previous.len() * std::mem::size_of::<f32>();
+        }
+        while self.used + size > self.budget {
+            let oldest = self
+                .chunks
+                .iter()
+                .min_by_key(|(_, entry)| entry.0)
+                .map(|(key, _)| key.clone());
+            match oldest.and_then(|key| self.chunks.remove(&key)) {
+                Some((_, evicted)) => self.used -= evicted.len() * std::mem::size_of::<f32>(),
+                None => break,
+            }
+        }
+        self.tick += 1;
+        self.used += size;
+        self.chunks.insert(key, (self.tick, values));
+    }
+}
+
+#[test]
+fn test_chunk_cache_eviction() {
+    let key = |i| ChunkKey { variable: "v".into(), slice: vec![0, 0], chunk: (i, 0) };
+    // room for 2 chunks of 4 values
+    let mut cache = ChunkCache::new(32);
+    cache.insert(key(0), Arc::new(vec![0.; 4]));
+    cache.insert(key(1), Arc::new(vec![1.; 4]));
+    // chunk 0 is now the most recently used
+    assert!(cache.get(&key(0)).is_some());
+    cache.insert(key(2), Arc::new(vec![2.; 4]));
+    assert!(cache.get(&key(1)).is_none());
+    assert_eq!(cache.get(&key(0)).unwrap()[0], 0.);
+    assert_eq!(cache.get(&key(2)).unwrap()[0], 2.);
+    assert_eq!(cache.used, 32);
+    // too large to be cached
+    cache.insert(key(3), Arc::new(vec![3.; 16]));
+    assert!(cache.get(&key(3)).is_none());
+}
@@ -0,0 +1,129 @@
+use std::cmp::Ordering;
+use std::f32;
+use colormap::ColorMap;
+use json;
+use scale::Scale;
+use stats::ZonalStatistics;
+
+/// A class of values, rendered with a single color
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+    /// Label displayed in the legend (eg: "gusts above 25 m/s")
+    pub label: String,
+    pub color: [u8; 4],
+}
+
+/// Splits values into classes using a list of ascending thresholds.
+///
+/// `n` thresholds define `n + 1` classes: the class `0` holds the values below the first
+/// threshold, the class `i` the values in [thresholds[i - 1]; thresholds[i]).
+///
+/// Use `scale()` and `color_map()` to build a `Renderer` drawing each class with its color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub thresholds: Vec<f32>,
+    pub classes: Vec<Class>,
+}
+
+impl Classification {
+    pub fn new(thresholds: Vec<f32>, classes: Vec<Class>) -> Result<Self, String> {
+        if classes.len() != thresholds.len() + 1 {
+            return Err("There must be one class more than thresholds".into());
+        }
+        if thresholds.windows(2).any(|pair| pair[0].partial_cmp(&pair[1]) != Some(Ordering::Less)) {
+            return Err("Thresholds must be sorted in ascending order".into());
+        }
+        Ok(Self { thresholds, classes })
+    }
+
+    /// Returns the class index of `value`, or None for NaN values
+    pub fn class_index(&self, value: f32) -> Option<usize> {
+        if value.is_nan() {
+            return None;
+        }
+        Some(class_index(&self.thresholds, value))
+    }
+
+    /// Returns the class of `value`, or None for NaN values
+    pub fn class(&self, value: f32) -> Option<&Class> {
+        self.class_index(value).map(|i| &self.classes[i])
+    }
+
+    /// Returns the highest class reached by `values`, or None if they are all NaN
+    pub fn max_class(&self, values: &[f32]) -> Option<usize> {
+        values.iter().filter_map(|v| self.class_index(*v)).max()
+    }
+
+    /// Returns the highest class reached inside a zone (see `Dataset::zonal_statistics`)
+    pub fn zone_class(&self, statistics: &ZonalStatistics) -> Option<usize> {
+        self.class_index(statistics.max)
+    }
+
+    /// Returns the scale mapping each class to its color
+    pub fn scale(&self) -> Scale {
+        Scale::Classified { thresholds: self.thresholds.clone() }
+    }
+
+    /// Returns the color map holding the color of each class
+    pub fn color_map(&self) -> ColorMap {
+        ColorMap::Classified(self.classes.iter().map(|class| class.color).collect())
+    }
+
+    /// Serializes the legend into JSON, as a list of
+    /// `{"label": .., "color": [r, g, b, a], "min": .., "max": ..}` objects
+    /// (the bounds of the first and the last classes are null).
+    pub fn legend_json(&self) -> String {
+        let items: Vec<String> = self
+            .classes
+            .iter()
+            .enumerate()
+            .map(|(i, class)| {
+                let min = if i == 0 { f32::NAN } else { self.thresholds[i - 1] };
+                let max = self.thresholds.get(i).cloned().unwrap_or(f32::NAN);
+                format!(
+                    "{{\"label\":{},\"color\":[{},{},{},{}],\"min\":{},\"max\":{}}}",
+                    json::string(&class.label),
+                    class.color[0],
+                    class.color[1],
+                    class.color[2],
+                    class.color[3],
+                    json::number(min),
+                    json::number(max)
+                )
+            })
+            .collect();
+        format!("[{}]", items.join(","))
+    }
+}
+
+/// Returns the number of thresholds lower than or equal to `value`
+pub fn class_index(thresholds: &[f32], value: f32) -> usize {
+    thresholds.iter().filter(|threshold| value >= **threshold).count()
+}
+
+#[test]
+fn test_classification() {
+    let class = |label: &str, color: [u8; 4]| Class { label: label.into(), color };
+    let classification = Classification::new(
+        vec![15., 25.],
+        vec![
+            class("calm", [0, 0, 0, 0]),
+            class("strong", [255, 200, 0, 255]),
+            class("severe", [255, 0, 0, 255]),
+        ],
+    ).unwrap();
+    assert_eq!(classification.class_index(10.), Some(0));
+    assert_eq!(classification.class_index(25.), Some(2));
+    assert_eq!(classification.class(f32::NAN), None);
+    assert_eq!(classification.max_class(&[3., f32::NAN, 17.]), Some(1));
+
+    // classes are rendered through the scale and the color map
+    let (scale, color_map) = (classification.scale(), classification.color_map());
+    let color = |value: f32| ::colormap::rgba(::scale::normalize(&scale, value), &color_map);
+    assert_eq!(color(10.), [0, 0, 0, 0]);
+    assert_eq!(color(20.), [255, 200, 0, 255]);
+    assert_eq!(color(30.), [255, 0, 0, 255]);
+
+    assert!(classification.legend_json().starts_with(r#"[{"label":"calm","color":[0,0,0,0],"min":null,"max":15}"#));
+    assert!(Classification::new(vec![25., 15.], classification.classes.clone()).is_err());
+}
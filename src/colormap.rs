@@ -130,6 +130,9 @@ pub enum ColorMap {
     BrBG_r,
     /// User defined
     Custom(CustomColormap),
+    /// Evenly splits [0; 1] into as many intervals as colors, without interpolation
+    /// (see `Classification`)
+    Classified(Vec<[u8; 4]>),
 }
 
 
@@ -192,6 +195,13 @@ pub fn rgba(value: f32, color_map: &ColorMap) -> [u8; 4] {
         ColorMap::Custom(ref cmap) => {
             cmap.value_to_color(value)
         },
+        ColorMap::Classified(ref colors) => {
+            if colors.is_empty() {
+                return [0u8; 4];
+            }
+            let idx = (value * colors.len() as f32).floor().max(0.) as usize;
+            colors[idx.min(colors.len() - 1)]
+        },
     }
 }
 
@@ -0,0 +1,247 @@
+use source::DataSource;
+use tiledata::TileGrid;
+use tile::Tile;
+use gapfill::GapFill;
+use filter::Filter;
+use mask::Mask;
+use alpha::AlphaRamp;
+use resampling::Resampling;
+use aggregation::Aggregation;
+use renderer::ImgTile;
+use pipeline::{Pipeline, check_tile_size};
+use scale::{Scale, normalize};
+
+/// A band of a composite image: a variable, its scale and its gamma correction.
+pub struct Band {
+    pub source: Box<dyn DataSource>,
+    pub scale: Scale,
+    /// Gamma correction, the normalized value `v` is rendered as `v^(1/gamma)`,
+    /// so a gamma above 1 brightens the band (1 means no correction).
+    pub gamma: f32,
+}
+
+impl Band {
+    pub fn new(source: Box<dyn DataSource>, scale: Scale) -> Self {
+        Self { source, scale, gamma: 1. }
+    }
+
+    /// Set the gamma correction, it must be strictly positive
+    pub fn with_gamma(mut self, gamma: f32) -> Result<Self, String> {
+        if gamma.is_nan() || gamma <= 0. {
+            return Err("The gamma correction must be strictly positive".into());
+        }
+        self.gamma = gamma;
+        Ok(self)
+    }
+
+    /// Returns the channel value (0 - 255) of a value, or None for NaN values
+    #[inline]
+    fn channel(&self, value: f32) -> Option<u8> {
+        if value.is_nan() {
+            return None;
+        }
+        let normalized = normalize(&self.scale, value).clamp(0., 1.);
+        Some((normalized.powf(1. / self.gamma) * 255.).round() as u8)
+    }
+}
+
+/// Renders true-color or false-color composites, (eg: satellite imagery),
+/// where the red, green and blue channels each come from a different variable.
+///
+/// Pixels where any band is missing are transparent.
+/// The bands are extracted, gap filled, resampled, filtered and masked like the variable
+/// of a `Renderer`, whose builders and rendering functions it mirrors.
+pub struct CompositeRenderer {
+    red: Band,
+    green: Band,
+    blue: Band,
+    alpha: Option<AlphaRamp>,
+    pipeline: Pipeline,
+}
+
+impl CompositeRenderer {
+    pub fn from_bands(red: Band, green: Band, blue: Band) -> Self {
+        Self { red, green, blue, alpha: None, pipeline: Pipeline::new() }
+    }
+
+    /// Fill the small gaps (NaN cells) of every band before rendering
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.pipeline.gap_fill = Some(gap_fill);
+        self
+    }
+
+    /// Smooth the regridded values of every band using `filter`
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.pipeline.filter = Some(filter);
+        self
+    }
+
+    /// Only render the pixels inside `mask`, others are left transparent
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.pipeline.mask = Some(mask);
+        self
+    }
+
+    /// Modulate the opacity of the pixels, `AlphaRamp::Value` ramps over
+    /// their brightness (the mean of the normalized bands, before the gamma correction)
+    pub fn with_alpha(mut self, alpha: AlphaRamp) -> Self {
+        self.alpha = Some(alpha);
+        self
+    }
+
+    /// Choose how the bands are resampled into the tile pixels (default: `Resampling::Auto`)
+    pub fn with_resampling(mut self, resampling: Resampling) -> Self {
+        self.pipeline.resampling = resampling;
+        self
+    }
+
+    /// Choose how the cells covered by a pixel are combined (see `Renderer::with_aggregation`)
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.pipeline.aggregation = aggregation;
+        self
+    }
+
+    /// Set the size (in pixels) of the rendered tiles (default: TILE_SIZE),
+    /// a power of 2 up to `MAX_TILE_SIZE`
+    pub fn with_tile_size(mut self, tile_size: usize) -> Result<Self, String> {
+        self.pipeline.tile_size = check_tile_size(tile_size)?;
+        Ok(self)
+    }
+
+    /// Set the number of threads used to render tile pyramids (default: 1)
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.pipeline.threads = threads.max(1);
+        self
+    }
+
+    fn bands(&self) -> [&Band; 3] {
+        [&self.red, &self.green, &self.blue]
+    }
+
+    fn sources(&self) -> [&dyn DataSource; 3] {
+        [&*self.red.source, &*self.green.source, &*self.blue.source]
+    }
+
+    /// Converts the grids of the red, green and blue bands into pixels
+    fn values_to_colors(&self, tile: &Tile, grids: &[TileGrid]) -> Vec<u8> {
+        let size = grids[0].size;
+        let bands = self.bands();
+        let mut colors = vec![0u8; 4 * size * size];
+        let mut count: usize = 0;
+        // iter latitude in reverse, to fit the image X,Y orientation
+        for i_lat in (0..size).rev() {
+            for i_lon in 0..size {
+                let mut rgba = [0u8, 0u8, 0u8, 255u8];
+                for (channel, (band, grid)) in bands.iter().zip(grids.iter()).enumerate() {
+                    match band.channel(grid[i_lat][i_lon]) {
+                        Some(value) => rgba[channel] = value,
+                        None => {
+                            rgba = [0u8; 4];
+                            break;
+                        }
+                    }
+                }
+                colors[count..count + 4].copy_from_slice(&rgba);
+                count += 4;
+            }
+        }
+        if let Some(ref alpha) = self.alpha {
+            let mut brightness = TileGrid::new(size);
+            for (k, value) in brightness.values.iter_mut().enumerate() {
+                let mut sum = 0.;
+                for (band, grid) in bands.iter().zip(grids.iter()) {
+                    sum += normalize(&band.scale, grid.values[k]).clamp(0., 1.);
+                }
+                *value = sum / 3.;
+            }
+            alpha.apply(tile, &brightness, &Scale::Linear { min: 0., max: 1. }, &mut colors);
+        }
+        colors
+    }
+
+    /// Render a Tile into an ImgTile.
+    pub fn render_tile(&self, tile: &Tile) -> Result<ImgTile, String> {
+        self.render_scaled_tile(tile, 1)
+    }
+
+    /// Render a Tile into an ImgTile `scale_factor` times larger (eg: 2 for @2x retina tiles).
+    pub fn render_scaled_tile(&self, tile: &Tile, scale_factor: usize) -> Result<ImgTile, String> {
+        self.pipeline.render_tile(&self.sources(), tile, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, grids)
+        })
+    }
+
+    /// Renders a tile and its `level` sub-levels into ImgTile,
+    /// extracting the values of each band only once (see `Renderer::render_n_level_tile`).
+    pub fn render_n_level_tile(&self, tile: &Tile, level: u8) -> Result<Vec<ImgTile>, String> {
+        self.render_n_level_scaled_tile(tile, level, 1)
+    }
+
+    /// Renders a tile and its `level` sub-levels, like `render_n_level_tile`,
+    /// into ImgTiles `scale_factor` times larger (eg: 2 for @2x retina tiles).
+    pub fn render_n_level_scaled_tile(&self, tile: &Tile, level: u8, scale_factor: usize) -> Result<Vec<ImgTile>, String> {
+        let mut imgs: Vec<ImgTile> = Vec::new();
+        self.render_n_level_tile_with(tile, level, scale_factor, |img| imgs.push(img))?;
+        Ok(imgs)
+    }
+
+    /// Renders a tile and its `level` sub-levels, handing each ImgTile to `callback`
+    /// as soon as it is rendered (see `Renderer::render_n_level_tile_with`).
+    pub fn render_n_level_tile_with<F>(&self, tile: &Tile, level: u8, scale_factor: usize, callback: F) -> Result<(), String>
+        where F: FnMut(ImgTile) {
+        self.pipeline.render_n_level_tile_with(&self.sources(), tile, level, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, grids)
+        }, callback)
+    }
+
+    /// Renders the block of `metatile_size` x `metatile_size` tiles containing `tile`
+    /// (see `Renderer::render_metatile`).
+    pub fn render_metatile(&self, tile: &Tile, metatile_size: u32, scale_factor: usize) -> Result<Vec<ImgTile>, String> {
+        self.pipeline.render_metatile(&self.sources(), tile, metatile_size, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, grids)
+        })
+    }
+}
+
+#[test]
+fn test_band_channel() {
+    let source = ::points::PointData::new(
+        &[(0., 0., 1.)],
+        ::points::PointInterpolation::InverseDistance { power: 2. },
+        1000.,
+    ).unwrap();
+    let band = Band::new(Box::new(source), Scale::Linear { min: 0., max: 100. });
+    assert_eq!(band.channel(0.), Some(0));
+    assert_eq!(band.channel(25.), Some(64));
+    assert_eq!(band.channel(200.), Some(255));
+    assert_eq!(band.channel(::std::f32::NAN), None);
+    let band = band.with_gamma(2.).unwrap();
+    assert_eq!(band.channel(25.), Some(128));
+    assert!(band.with_gamma(0.).is_err());
+}
+
+#[test]
+fn test_composite_render_tile() {
+    use points::{PointData, PointInterpolation};
+    // a single point covers the whole tile with its value
+    let band = |lon: f64, lat: f64, value: f32, max_distance: f64| {
+        let source = PointData::new(&[(lon, lat, value)], PointInterpolation::InverseDistance { power: 2. }, max_distance);
+        Band::new(Box::new(source.unwrap()), Scale::Linear { min: 0., max: 100. })
+    };
+    let renderer = CompositeRenderer::from_bands(
+        band(90., 45., 25., 2e7),
+        band(90., 45., 100., 2e7),
+        // only around the south west corner of the tile
+        band(0., 0., 50., 1e6),
+    ).with_tile_size(64).unwrap();
+    // north east quarter of the world
+    let img = renderer.render_tile(&Tile { x: 1, y: 0, z: 1 }).unwrap();
+    assert_eq!(img.size, 64);
+    let pixel = |row: usize, col: usize| &img.pixels[4 * (row * 64 + col)..4 * (row * 64 + col + 1)];
+    assert_eq!(pixel(63, 0), &[64, 255, 128, 255]);
+    // the blue band is missing in the north west corner
+    assert_eq!(pixel(0, 0), &[0, 0, 0, 0]);
+    let imgs = renderer.render_n_level_scaled_tile(&Tile { x: 1, y: 0, z: 1 }, 1, 2).unwrap();
+    assert_eq!(imgs.len(), 5);
+    assert!(imgs.iter().all(|img| img.size == 128));
+}
@@ -1,27 +1,181 @@
 use netcdf;
 use netcdf::attribute::AttrValue;
 use netcdf::file::File as NcFile;
-use tile::{lat_wgs84_to_meters, lon_wgs84_to_meters, wgs84_to_meters, Bbox, Tile};
+use netcdf::variable::Variable;
+use tile::{
+    lat_meters_to_wgs84, lat_wgs84_to_meters, lon_meters_to_wgs84, lon_wgs84_to_meters,
+    wgs84_to_meters, Bbox, LonLatBbox, Tile,
+};
 //use tile::{Tile,LonLatBbox,lat_to_pixel,lon_to_pixel};
 use std::f32;
-use tiledata::TileData;
-use utils::{search_closest_idx, search_closest_idx_below, search_closest_idx_over};
+use std::sync::{Arc, Mutex};
+use aggregation::Aggregation;
+use cache::{ChunkCache, ChunkKey, CHUNK_SIZE};
+use overview::{build_overviews, load_overviews, save_overviews, Overview};
+use std::fs;
+use std::path::Path;
+use tiledata::{TileData, TILE_SIZE};
+use timeseries::{parse_time_units, TimeSeries};
+use transect::{sample_path, Transect};
+use geojson::Zone;
+use stats::{Statistics, ZonalStatistics};
+use summary::{DatasetSummary, DimensionInfo, VariableInfo};
+use units::{conversion, UnitConversion};
+use utils::{cell_edges, search_closest_idx, search_closest_idx_below, search_closest_idx_over};
 
-fn format_error(error: netcdf::error::Error) -> String {
+pub(crate) fn format_error(error: netcdf::error::Error) -> String {
     format!("{:?}", error)
 }
 
+/// Returns the `_FillValue` attribute of a variable, if any
+pub(crate) fn variable_fill_value(variable: &Variable) -> Option<f32> {
+    match variable.attribute("_FillValue")?.value() {
+        Ok(AttrValue::Float(x)) => Some(x),
+        Ok(AttrValue::Double(x)) => Some(x as f32),
+        _ => None,
+    }
+}
+
+/// Returns a string attribute of a variable, if any
+pub(crate) fn variable_string_attribute(variable: &Variable, name: &str) -> Option<String> {
+    match variable.attribute(name)?.value() {
+        Ok(AttrValue::Str(x)) => Some(x),
+        _ => None,
+    }
+}
+
+/// Read the values of a variable between the `start` and `start + count` indices,
+/// and replace its fill values by NaN.
+fn read_window(
+    variable: &Variable,
+    start: &[usize],
+    count: &[usize],
+) -> Result<Vec<f32>, String> {
+    let mut values: Vec<f32> = vec![f32::NAN; count.iter().product()];
+    variable
+        .values_to(values.as_mut_slice(), Some(start), Some(count))
+        .map_err(format_error)?;
+    // Filter fill_values
+    if let Some(fill_value) = variable_fill_value(variable) {
+        for v in values.iter_mut() {
+            if *v == fill_value {
+                *v = f32::NAN;
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Returns the cell edges of a coordinate variable (`centers.len() + 1` values),
+/// read from its CF `bounds` variable (eg: `lat_bnds`) if any,
+/// or inferred from the midpoints between the cell centers.
+fn coordinate_edges(file: &NcFile, coordinate: &Variable, centers: &[f64]) -> Result<Vec<f64>, String> {
+    let n = centers.len();
+    let bounds_var = variable_string_attribute(coordinate, "bounds")
+        .and_then(|name| file.variable(&name))
+        .filter(|bounds_var| n > 0 && bounds_var.len() == 2 * n);
+    let bounds_var = match bounds_var {
+        Some(bounds_var) => bounds_var,
+        None => return Ok(cell_edges(centers)),
+    };
+    let mut bounds: Vec<f64> = vec![0.; 2 * n];
+    bounds_var
+        .values_to(bounds.as_mut_slice(), None, None)
+        .map_err(format_error)?;
+    // the lower edge of each cell, then the upper edge of the last one
+    let ascending = n < 2 || centers[0] < centers[n - 1];
+    let lower = |cell: &[f64]| if ascending { cell[0].min(cell[1]) } else { cell[0].max(cell[1]) };
+    let upper = |cell: &[f64]| if ascending { cell[0].max(cell[1]) } else { cell[0].min(cell[1]) };
+    let mut edges: Vec<f64> = bounds.chunks(2).map(lower).collect();
+    edges.push(upper(&bounds[(2 * n - 2)..]));
+    Ok(edges)
+}
+
+/**
+ * Returns the indices (i_lat_min, i_lat_max, i_lon_min, i_lon_max)
+ * of the smallest window of a (lat, lon) grid containing the bounding box.
+ */
+fn window_indices(lat: &[f64], lon: &[f64], bbox: &Bbox) -> Result<(usize, usize, usize, usize), String> {
+    // get longitude indices containing the bounding box
+    let mut i_lon_min: usize =
+        search_closest_idx_below(lon, bbox.west).ok_or(format!("Longitude error"))?;
+    let mut i_lon_max: usize =
+        search_closest_idx_over(lon, bbox.east).ok_or(format!("Longitude error"))?;
+    if i_lon_max < i_lon_min {
+        let tmp = i_lon_max;
+        i_lon_max = i_lon_min;
+        i_lon_min = tmp;
+    }
+
+    // get latitude indices containing the bounding box
+    let mut i_lat_min: usize =
+        search_closest_idx_below(lat, bbox.south).ok_or(format!("Latitude error"))?;
+    let mut i_lat_max: usize =
+        search_closest_idx_over(lat, bbox.north).ok_or(format!("Latitude error"))?;
+    if i_lat_max < i_lat_min {
+        let tmp = i_lat_max;
+        i_lat_max = i_lat_min;
+        i_lat_min = tmp;
+    }
+    Ok((i_lat_min, i_lat_max, i_lon_min, i_lon_max))
+}
+
+/// Pack the window of a (lat, lon) grid extracted for a tile into a TileData
+fn window_tile_data(
+    tile: &Tile,
+    bbox: Bbox,
+    lat: &[f64],
+    lat_edges: &[f64],
+    lon: &[f64],
+    lon_edges: &[f64],
+    values: Vec<f32>,
+) -> TileData {
+    TileData {
+        min_lon: lon[0].min(lon[lon.len() - 1]),
+        max_lon: lon[0].max(lon[lon.len() - 1]),
+        lon: lon.to_vec(),
+        min_lat: lat[0].min(lat[lat.len() - 1]),
+        max_lat: lat[0].max(lat[lat.len() - 1]),
+        lat: lat.to_vec(),
+        lon_edges: lon_edges.to_vec(),
+        lat_edges: lat_edges.to_vec(),
+        values,
+        bbox,
+        tile: Tile {
+            x: tile.x,
+            y: tile.y,
+            z: tile.z,
+        },
+    }
+}
+
 /// This Struct provides access to the data within a netCDF file.
 pub struct Dataset {
     // meter (Web Mercator)
     lat: Vec<f64>,
+    // cell edges, meter (Web Mercator)
+    lat_edges: Vec<f64>,
+    // extent of the cell edges
     min_lat: f64,
     max_lat: f64,
     // meter (Web Mercator)
     lon: Vec<f64>,
+    // cell edges, meter (Web Mercator)
+    lon_edges: Vec<f64>,
+    // extent of the cell edges
     min_lon: f64,
     max_lon: f64,
+    // name of the latitude and longitude dimensions
+    lat_dim: String,
+    lon_dim: String,
     variable_name: String,
+    // conversion applied to the values of `variable_name`, see `Dataset::with_units`
+    units: Option<String>,
+    conversion: Option<UnitConversion>,
+    // decoded chunks of values, see `Dataset::with_cache`
+    cache: Option<Mutex<ChunkCache>>,
+    // decimated copies of the variable (unconverted values), see `Dataset::with_overviews`
+    overviews: Vec<Overview>,
     file: NcFile,
 }
 
@@ -40,7 +194,11 @@ impl Dataset {
     ///
     /// * The longitude and latitude variable must be sorted in ascending order.
     /// * The longitude and latitude variable must be projected in *WGS 84 (srs 4326)*.
-    /// * values of `variable` must be bi-dimensionals (lat, lon)
+    /// * values of `variable` must be indexed by (lat, lon), any other dimension (eg: time)
+    ///   is read at its first index.
+    ///
+    /// The cell edges are read from the CF `bounds` variables of the longitude and latitude
+    /// (eg: `lat_bnds`), or inferred from the midpoints between the coordinates.
     ///
     pub fn new(
         latitude: &str,
@@ -52,6 +210,7 @@ impl Dataset {
         let root = file.root().ok_or("No root group")?;
 
         let lat_var = root.variable(latitude).ok_or("No latitude")?;
+        let lat_dim = lat_var.dimensions().first().ok_or("No latitude dimension")?.name();
 
         let size = lat_var.len();
         let mut lat: Vec<f64> = unsafe {
@@ -62,12 +221,17 @@ impl Dataset {
         lat_var
             .values_to(lat.as_mut_slice(), None, None)
             .map_err(format_error)?;
+        let lat_edges: Vec<f64> = coordinate_edges(&file, &lat_var, &lat)?
+            .into_iter()
+            .map(|y| lat_wgs84_to_meters(y.clamp(-90., 90.)))
+            .collect();
         // convert WGS84 to WebMercator
         for y in lat.iter_mut() {
             *y = lat_wgs84_to_meters(*y);
         }
 
         let lon_var = root.variable(longitude).ok_or("No lonitude")?;
+        let lon_dim = lon_var.dimensions().first().ok_or("No longitude dimension")?.name();
         let size = lon_var.len();
         let mut lon: Vec<f64> = unsafe {
             let mut v = Vec::with_capacity(size);
@@ -77,18 +241,33 @@ impl Dataset {
         lon_var
             .values_to(lon.as_mut_slice(), None, None)
             .map_err(format_error)?;
+        let lon_edges: Vec<f64> = coordinate_edges(&file, &lon_var, &lon)?
+            .into_iter()
+            .map(lon_wgs84_to_meters)
+            .collect();
         // convert WGS84 to WebMercator
         for x in lon.iter_mut() {
             *x = lon_wgs84_to_meters(*x);
         }
+        let (lat_n, lon_n) = (lat_edges.len() - 1, lon_edges.len() - 1);
         Ok(Self {
-            min_lat: lat[0].min(lat[lat.len() - 1]),
-            max_lat: lat[0].max(lat[lat.len() - 1]),
+            min_lat: lat_edges[0].min(lat_edges[lat_n]),
+            max_lat: lat_edges[0].max(lat_edges[lat_n]),
             lat: lat,
-            min_lon: lon[0].min(lon[lon.len() - 1]),
-            max_lon: lon[0].max(lon[lon.len() - 1]),
+            lat_edges,
+            min_lon: lon_edges[0].min(lon_edges[lon_n]),
+            max_lon: lon_edges[0].max(lon_edges[lon_n]),
             lon: lon,
+            lon_edges,
+            lat_dim,
+            lon_dim,
             variable_name: variable.into(),
+            units: root
+                .variable(variable)
+                .and_then(|var| variable_string_attribute(&var, "units")),
+            conversion: None,
+            cache: None,
+            overviews: Vec::new(),
             file: file,
         })
     }
@@ -97,16 +276,247 @@ impl Dataset {
      * Get the fill value of the dataset
      */
     pub fn get_fill_value(&self) -> Option<f32> {
-        if let Some(var) = self.file.root()?.variable(&self.variable_name) {
-            if let Some(attr) = var.attribute("_FillValue") {
-                match attr.value() {
-                    Ok(AttrValue::Float(x)) => Some(x),
-                    Ok(AttrValue::Double(x)) => Some(x as f32),
-                    _ => None,
+        let root = self.file.root()?;
+        let variable = root.variable(&self.variable_name)?;
+        variable_fill_value(&variable)
+    }
+
+    /// Converts the values of the dataset variable into `target` units
+    /// (eg: "degC", "knots", "mm/h") whenever they are read,
+    /// using the variable `units` attribute as the source unit.
+    ///
+    /// Supported quantities are temperatures, speeds, precipitation fluxes and
+    /// accumulations, lengths, pressures and ratios.
+    pub fn with_units(mut self, target: &str) -> Result<Self, String> {
+        let source = {
+            let root = self.file.root().ok_or("No root group !")?;
+            let variable = root.variable(&self.variable_name).ok_or("No variable found")?;
+            variable_string_attribute(&variable, "units").ok_or("The variable has no units")?
+        };
+        self.conversion = Some(
+            conversion(&source, target)
+                .ok_or_else(|| format!("Can not convert {} into {}", source, target))?,
+        );
+        self.units = Some(target.into());
+        Ok(self)
+    }
+
+    /// Keeps up to `budget` bytes of decoded values in memory (eg: 256 MB),
+    /// so the overlapping windows of neighbouring tiles are only read once from the file.
+    ///
+    /// The (latitude, longitude) slices of the variables are read by chunks of
+    /// CHUNK_SIZE x CHUNK_SIZE cells, the least recently used chunks are dropped first.
+    pub fn with_cache(mut self, budget: usize) -> Self {
+        self.cache = Some(Mutex::new(ChunkCache::new(budget)));
+        self
+    }
+
+    /**
+     * Builds overviews of the dataset variable: decimated copies of its (lat, lon) grid,
+     * each one aggregating the cells of the previous one 2 x 2, down to a single cell.
+     * The tiles of low zoom levels are then extracted from the coarsest overview
+     * which still resolves their pixels, instead of reading and aggregating the whole grid.
+     *
+     * #Args
+     *  * `sidecar`: path of a file where the overviews are persisted (eg: "data.nc.ovr"),
+     *    they are loaded from it when it is up to date, otherwise built and written to it.
+     *  * `aggregation`: how the cells are combined, it should match the aggregation of the
+     *    renderer (eg: `Aggregation::Max` keeps the peaks seen by `Renderer::max_class`),
+     *    as a mean of the overview cells can't be turned back into a maximum.
+     */
+    pub fn with_overviews(mut self, sidecar: Option<&str>, aggregation: Aggregation) -> Result<Self, String> {
+        let shape = (self.lat.len(), self.lon.len());
+        if let Some(path) = sidecar {
+            if self.is_older_than(path) {
+                if let Ok(overviews) = load_overviews(path, &self.variable_name, shape, &aggregation) {
+                    self.overviews = overviews;
+                    return Ok(self);
+                }
+            }
+        }
+        let values = self.read_raw_values(
+            &self.variable_name,
+            &[(&self.lat_dim, 0, shape.0), (&self.lon_dim, 0, shape.1)],
+        )?;
+        self.overviews = build_overviews(Overview {
+            lat: self.lat.clone(),
+            lat_edges: self.lat_edges.clone(),
+            lon: self.lon.clone(),
+            lon_edges: self.lon_edges.clone(),
+            values,
+        }, &aggregation);
+        if let Some(path) = sidecar {
+            save_overviews(path, &self.variable_name, shape, &aggregation, &self.overviews)?;
+        }
+        Ok(self)
+    }
+
+    /// Check if the netCDF file was not modified after the file at `path`
+    /// (when the modification times are unknown, assume it was, so the file is rebuilt)
+    fn is_older_than(&self, path: &str) -> bool {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let dataset_modified = self.file.path().ok().and_then(|path| modified(&path));
+        match (dataset_modified, modified(Path::new(path))) {
+            (Some(dataset), Some(sidecar)) => dataset <= sidecar,
+            _ => false,
+        }
+    }
+
+    /// Returns the units of the values returned by the dataset, if known
+    pub fn units(&self) -> Option<&str> {
+        self.units.as_deref()
+    }
+
+    /**
+     * Read the values of a variable inside a window of indices.
+     *
+     * Each window is expressed as (dimension name, start index, count),
+     * the dimensions which are not listed are read at their first index.
+     * Fill values are replaced by NaN, and the values of the dataset variable
+     * are converted into the requested units.
+     *
+     * When the dataset has a cache, (latitude, longitude) slices are read by chunks.
+     */
+    fn read_values(
+        &self,
+        variable_name: &str,
+        windows: &[(&str, usize, usize)],
+    ) -> Result<Vec<f32>, String> {
+        let mut values = self.read_raw_values(variable_name, windows)?;
+        self.convert(variable_name, &mut values);
+        Ok(values)
+    }
+
+    /// Converts values of `variable_name` into the requested units
+    /// (only the dataset variable is converted)
+    fn convert(&self, variable_name: &str, values: &mut [f32]) {
+        if let Some(conversion) = self.conversion {
+            if variable_name == self.variable_name {
+                for v in values.iter_mut() {
+                    *v = conversion.apply(*v);
+                }
+            }
+        }
+    }
+
+    /// Same as `read_values`, without the unit conversion
+    fn read_raw_values(
+        &self,
+        variable_name: &str,
+        windows: &[(&str, usize, usize)],
+    ) -> Result<Vec<f32>, String> {
+        let root = self.file.root().ok_or("No root group !")?;
+        let variable = root
+            .variable(variable_name)
+            .ok_or_else(|| format!("No variable named {}", variable_name))?;
+
+        let mut start: Vec<usize> = Vec::with_capacity(variable.dimensions().len());
+        let mut count: Vec<usize> = Vec::with_capacity(variable.dimensions().len());
+        for dimension in variable.dimensions() {
+            let name = dimension.name();
+            match windows.iter().find(|window| window.0 == name) {
+                Some(&(_, dim_start, dim_count)) => {
+                    start.push(dim_start);
+                    count.push(dim_count);
+                }
+                None => {
+                    start.push(0);
+                    count.push(1);
+                }
+            }
+        }
+        if let Some(ref cache) = self.cache {
+            let chunked: Vec<usize> = variable
+                .dimensions()
+                .iter()
+                .enumerate()
+                .filter(|(_, dimension)| {
+                    let name = dimension.name();
+                    name == self.lat_dim || name == self.lon_dim
+                })
+                .map(|(i, _)| i)
+                .collect();
+            let is_slice = (0..count.len()).all(|i| chunked.contains(&i) || count[i] == 1);
+            if chunked.len() == 2 && is_slice {
+                return self.read_chunked_values(
+                    cache,
+                    &variable,
+                    variable_name,
+                    &start,
+                    &count,
+                    (chunked[0], chunked[1]),
+                );
+            }
+        }
+        read_window(&variable, &start, &count)
+    }
+
+    /**
+     * Read a (latitude, longitude) slice of a variable from the cached chunks
+     * (reading the missing ones), `dims` being the position of the latitude and
+     * longitude dimensions, in the variable dimension order.
+     */
+    fn read_chunked_values(
+        &self,
+        cache: &Mutex<ChunkCache>,
+        variable: &Variable,
+        variable_name: &str,
+        start: &[usize],
+        count: &[usize],
+        dims: (usize, usize),
+    ) -> Result<Vec<f32>, String> {
+        let (d0, d1) = dims;
+        let (n0, n1) = (count[d0], count[d1]);
+        let mut values: Vec<f32> = vec![f32::NAN; n0 * n1];
+        if values.is_empty() {
+            return Ok(values);
+        }
+        let lengths: Vec<usize> = variable.dimensions().iter().map(|d| d.len()).collect();
+        let mut slice = start.to_vec();
+        slice[d0] = 0;
+        slice[d1] = 0;
+
+        for c0 in (start[d0] / CHUNK_SIZE)..=((start[d0] + n0 - 1) / CHUNK_SIZE) {
+            for c1 in (start[d1] / CHUNK_SIZE)..=((start[d1] + n1 - 1) / CHUNK_SIZE) {
+                // extent of the chunk
+                let (o0, o1) = (c0 * CHUNK_SIZE, c1 * CHUNK_SIZE);
+                let (m0, m1) = (CHUNK_SIZE.min(lengths[d0] - o0), CHUNK_SIZE.min(lengths[d1] - o1));
+                let key = ChunkKey {
+                    variable: variable_name.into(),
+                    slice: slice.clone(),
+                    chunk: (c0, c1),
+                };
+                let cached = cache.lock().map_err(|_| "The chunk cache is poisoned")?.get(&key);
+                let chunk = match cached {
+                    Some(chunk) => chunk,
+                    None => {
+                        let mut chunk_start = slice.clone();
+                        chunk_start[d0] = o0;
+                        chunk_start[d1] = o1;
+                        let mut chunk_count = vec![1; count.len()];
+                        chunk_count[d0] = m0;
+                        chunk_count[d1] = m1;
+                        let chunk = Arc::new(
+                            read_window(variable, &chunk_start, &chunk_count)?,
+                        );
+                        cache
+                            .lock()
+                            .map_err(|_| "The chunk cache is poisoned")?
+                            .insert(key, chunk.clone());
+                        chunk
+                    }
                 };
+                // copy the intersection of the chunk and the window
+                let (k0, k1) = (start[d1].max(o1), (start[d1] + n1).min(o1 + m1));
+                for r in start[d0].max(o0)..(start[d0] + n0).min(o0 + m0) {
+                    let row = (r - start[d0]) * n1;
+                    let chunk_row = (r - o0) * m1;
+                    values[(row + k0 - start[d1])..(row + k1 - start[d1])]
+                        .copy_from_slice(&chunk[(chunk_row + k0 - o1)..(chunk_row + k1 - o1)]);
+                }
             }
         }
-        None
+        Ok(values)
     }
 
     /**
@@ -142,126 +552,430 @@ impl Dataset {
         return true;
     }
 
+    /**
+     * Returns the indices (i_lat_min, i_lat_max, i_lon_min, i_lon_max)
+     * of the smallest window of the dataset containing the bounding box.
+     */
+    fn window_indices(&self, bbox: &Bbox) -> Result<(usize, usize, usize, usize), String> {
+        window_indices(&self.lat, &self.lon, bbox)
+    }
+
     /**
      * Extract data from the netCDF dataset
      * and pack it into a TileData
      */
     pub fn get_tile_data(&self, tile: &Tile) -> Result<TileData, String> {
+        self.get_buffered_tile_data(tile, 0)
+    }
+
+    /**
+     * Extract the data of a tile, plus a border of `buffer` pixels around it
+     * (so it can be filtered without seams), and pack it into a TileData
+     */
+    pub fn get_buffered_tile_data(&self, tile: &Tile, buffer: usize) -> Result<TileData, String> {
+        self.get_sized_tile_data(tile, buffer, TILE_SIZE)
+    }
+
+    /**
+     * Extract the data of a tile rendered into `size` x `size` pixels
+     * (plus a border of `buffer` pixels of a TILE_SIZE tile around it),
+     * from the coarsest overview resolving those pixels if any, see `Dataset::with_overviews`.
+     */
+    pub fn get_sized_tile_data(&self, tile: &Tile, buffer: usize, size: usize) -> Result<TileData, String> {
         let bbox = tile.xy_bounds();
         if !self.contains_bbox(&bbox) {
             return Err("tile outside range".into());
         }
 
-        // get longitude indices containing the tile data
-        let mut i_lon_min: usize =
-            search_closest_idx_below(&self.lon, bbox.west).ok_or(format!("Longitude error"))?;
-        let mut i_lon_max: usize =
-            search_closest_idx_over(&self.lon, bbox.east).ok_or(format!("Longitude error"))?;
-        if i_lon_max < i_lon_min {
-            let tmp = i_lon_max;
-            i_lon_max = i_lon_min;
-            i_lon_min = tmp;
+        if let Some(tile_data) = self.overview_tile_data(tile, &bbox, buffer, size)? {
+            return Ok(tile_data);
         }
 
-        // get latitude indices containing the tile data
-        let mut i_lat_min: usize =
-            search_closest_idx_below(&self.lat, bbox.south).ok_or(format!("Latitude error"))?;
-        let mut i_lat_max: usize =
-            search_closest_idx_over(&self.lat, bbox.north).ok_or(format!("Latitude error"))?;
-        if i_lat_max < i_lat_min {
-            let tmp = i_lat_max;
-            i_lat_max = i_lat_min;
-            i_lat_min = tmp;
-        }
+        let (i_lat_min, i_lat_max, i_lon_min, i_lon_max) =
+            self.window_indices(&bbox.buffered(buffer))?;
         // Extract data from the netCDF Dataset
-        if let Some(variable) = self
-            .file
-            .root()
-            .ok_or("No root group !")?
-            .variable(&self.variable_name)
-        {
-            // Compute values slice size (must be > 0)
-            let slice_size = [i_lat_max - i_lat_min + 1, i_lon_max - i_lon_min + 1];
-            let size = slice_size[0] * slice_size[1];
-
-            let mut var_values: Vec<f32> = unsafe {
-                let mut v = Vec::with_capacity(size);
-                v.set_len(size);
-                v
-            };
-            variable
-                .values_to(
-                    var_values.as_mut_slice(),
-                    Some(&[i_lat_min, i_lon_min]), // start of the data slice
-                    Some(&slice_size),             // size of the data slice
-                )
-                .map_err(format_error)?;
-            // Filter fill_values
-            if let Some(fill_value) = self.get_fill_value() {
-                for v in var_values.iter_mut() {
-                    if *v == fill_value {
-                        *v = f32::NAN;
-                    }
-                }
+        let var_values = self.read_values(
+            &self.variable_name,
+            &[
+                (&self.lat_dim, i_lat_min, i_lat_max - i_lat_min + 1),
+                (&self.lon_dim, i_lon_min, i_lon_max - i_lon_min + 1),
+            ],
+        )?;
+        Ok(window_tile_data(
+            tile,
+            bbox,
+            &self.lat[i_lat_min..(i_lat_max + 1)],
+            &self.lat_edges[i_lat_min..(i_lat_max + 2)],
+            &self.lon[i_lon_min..(i_lon_max + 1)],
+            &self.lon_edges[i_lon_min..(i_lon_max + 2)],
+            var_values,
+        ))
+    }
+
+    /**
+     * Extract the data of a tile (plus a border of `buffer` pixels) from the coarsest
+     * overview whose cells are smaller than half a pixel, the tile being rendered
+     * into `size` x `size` pixels, if any.
+     */
+    fn overview_tile_data(&self, tile: &Tile, bbox: &Bbox, buffer: usize, size: usize) -> Result<Option<TileData>, String> {
+        let max_spacing = (bbox.east - bbox.west).abs() / (2 * size.max(1)) as f64;
+        let buffered = bbox.buffered(buffer);
+        for overview in self.overviews.iter().rev() {
+            let (i_lat_min, i_lat_max, i_lon_min, i_lon_max) =
+                window_indices(&overview.lat, &overview.lon, &buffered)?;
+            let (width, height) = overview.spacing(i_lat_min, i_lat_max, i_lon_min, i_lon_max);
+            if width > max_spacing || height > max_spacing {
+                continue;
             }
-            // Pick the associated lon /lat values
-            let lon: Vec<f64> = self.lon[i_lon_min..(i_lon_max + 1)]
-                .iter()
-                .map(|x| *x)
-                .collect();
-            let lat: Vec<f64> = self.lat[i_lat_min..(i_lat_max + 1)]
-                .iter()
-                .map(|x| *x)
-                .collect();
+            let n_lon = overview.lon.len();
+            let mut values: Vec<f32> = Vec::new();
+            for i in i_lat_min..(i_lat_max + 1) {
+                values.extend_from_slice(&overview.values[(i * n_lon + i_lon_min)..(i * n_lon + i_lon_max + 1)]);
+            }
+            self.convert(&self.variable_name, &mut values);
+            return Ok(Some(window_tile_data(
+                tile,
+                tile.xy_bounds(),
+                &overview.lat[i_lat_min..(i_lat_max + 1)],
+                &overview.lat_edges[i_lat_min..(i_lat_max + 2)],
+                &overview.lon[i_lon_min..(i_lon_max + 1)],
+                &overview.lon_edges[i_lon_min..(i_lon_max + 2)],
+                values,
+            )));
+        }
+        Ok(None)
+    }
 
-            return Ok(TileData {
-                min_lon: lon[0].min(lon[lon.len() - 1]),
-                max_lon: lon[0].max(lon[lon.len() - 1]),
-                lon: lon,
-                min_lat: lat[0].min(lat[lat.len() - 1]),
-                max_lat: lat[0].max(lat[lat.len() - 1]),
-                lat: lat,
-                values: var_values,
-                bbox: bbox,
-                tile: Tile {
-                    x: tile.x,
-                    y: tile.y,
-                    z: tile.z,
-                },
-            });
+    /// Return the indices (lat, lon) of the grid point closest to (lat, lon) (WGS84)
+    fn closest_indices(&self, lat: f64, lon: f64) -> Result<(usize, usize), String> {
+        // transform (lat, lon) into Web Mercator (as self.lat and self.lon)
+        let (x, y) = wgs84_to_meters(lon, lat);
+        if !self.contains_point(y, x) {
+            return Err("Point outside of the dataset".into());
         }
-        Err("Error while fetching tile, no variable found".into())
+        let lon_idx: usize =
+            search_closest_idx(&self.lon, x).ok_or_else(|| format!("longitude error"))?;
+        let lat_idx: usize =
+            search_closest_idx(&self.lat, y).ok_or_else(|| format!("latitude error"))?;
+        Ok((lat_idx, lon_idx))
     }
 
     /// Return the value stored at (lat, lon)
     pub fn value_at_coordinates(&self, lat: f64, lon: f64) -> Result<f32, String> {
-        // transform (lat, lon) into Web Mercator (as self.lat and self.lon)
-        let (x, y) = wgs84_to_meters(lon, lat);
-        if self.contains_point(y, x) {
-            // fetch the closest point in the dataset
-            let lon_idx: usize =
-                search_closest_idx(&self.lon, x).ok_or_else(|| format!("longitude error"))?;
-            let lat_idx: usize =
-                search_closest_idx(&self.lat, y).ok_or_else(|| format!("latitude error"))?;
-            // extract it value
-            if let Some(variable) = self
-                .file
-                .root()
-                .ok_or("No root !")?
-                .variable(&self.variable_name)
-            {
-                let value = variable
-                    .value::<f32>(Some(&[lat_idx, lon_idx]))
-                    .map_err(format_error)?;
-                if let Some(fill_value) = self.get_fill_value() {
-                    if value == fill_value {
-                        return Ok(f32::NAN);
+        // fetch the closest point in the dataset
+        let (lat_idx, lon_idx) = self.closest_indices(lat, lon)?;
+        // extract it value
+        let values = self.read_values(
+            &self.variable_name,
+            &[(&self.lat_dim, lat_idx, 1), (&self.lon_dim, lon_idx, 1)],
+        )?;
+        values.first().cloned().ok_or_else(|| "Dataset error".into())
+    }
+
+    /// Extract the time series of one or several variables at (lat, lon),
+    /// reading the whole time dimension at once.
+    ///
+    /// #Args
+    ///  * `time` name of the time variable, its `units` attribute must follow
+    ///    the CF conventions (eg: "hours since 2017-08-01 00:00:00")
+    ///  * `lat` latitude of the point (WGS84)
+    ///  * `lon` longitude of the point (WGS84)
+    ///  * `variables` names of the variables to extract,
+    ///    the dataset variable is used if empty. They must all be indexed by `time`.
+    pub fn time_series(
+        &self,
+        time: &str,
+        lat: f64,
+        lon: f64,
+        variables: &[&str],
+    ) -> Result<TimeSeries, String> {
+        let (lat_idx, lon_idx) = self.closest_indices(lat, lon)?;
+
+        // read the time steps, and turn them into timestamps
+        let root = self.file.root().ok_or("No root group !")?;
+        let time_var = root.variable(time).ok_or("No time variable")?;
+        let time_dim = time_var.dimensions().first().ok_or("No time dimension")?.name();
+        let units = variable_string_attribute(&time_var, "units").ok_or("No time units")?;
+        let (step, reference) =
+            parse_time_units(&units).ok_or_else(|| format!("Unsupported time units: {}", units))?;
+        let mut steps: Vec<f64> = vec![0.; time_var.len()];
+        time_var
+            .values_to(steps.as_mut_slice(), None, None)
+            .map_err(format_error)?;
+        let timestamps: Vec<i64> = steps
+            .iter()
+            .map(|t| reference + (t * step).round() as i64)
+            .collect();
+
+        let names: Vec<&str> = if variables.is_empty() {
+            vec![&self.variable_name]
+        } else {
+            variables.to_vec()
+        };
+        let mut values: Vec<Vec<f32>> = Vec::with_capacity(names.len());
+        for name in &names {
+            let series = self.read_values(
+                name,
+                &[
+                    (&time_dim, 0, timestamps.len()),
+                    (&self.lat_dim, lat_idx, 1),
+                    (&self.lon_dim, lon_idx, 1),
+                ],
+            )?;
+            // eg: the variable is not indexed by the time dimension
+            if series.len() != timestamps.len() {
+                return Err(format!(
+                    "{} has {} values instead of {} time steps",
+                    name,
+                    series.len(),
+                    timestamps.len()
+                ));
+            }
+            values.push(series);
+        }
+
+        Ok(TimeSeries {
+            lon: lon_meters_to_wgs84(self.lon[lon_idx]),
+            lat: lat_meters_to_wgs84(self.lat[lat_idx]),
+            timestamps,
+            variables: names.iter().map(|name| name.to_string()).collect(),
+            values,
+        })
+    }
+
+    /// Sample values along a path (polyline), optionally across a vertical
+    /// dimension to form a cross-section.
+    ///
+    /// #Args
+    ///  * `path` vertices of the path, as (lon, lat) pairs (WGS84)
+    ///  * `spacing` distance between two samples (meters)
+    ///  * `vertical` name of the vertical coordinate variable (eg: "depth"),
+    ///    all of its levels are read for each sample. The dataset variable must be indexed by it.
+    pub fn transect(
+        &self,
+        path: &[(f64, f64)],
+        spacing: f64,
+        vertical: Option<&str>,
+    ) -> Result<Transect, String> {
+        if path.is_empty() {
+            return Err("Empty path".into());
+        }
+        if spacing.is_nan() || spacing <= 0. {
+            return Err("The spacing must be strictly positive".into());
+        }
+
+        // read the vertical coordinate, if any
+        let mut levels: Vec<f64> = Vec::new();
+        let mut vertical_dim: Option<String> = None;
+        if let Some(name) = vertical {
+            let root = self.file.root().ok_or("No root group !")?;
+            let vertical_var = root.variable(name).ok_or("No vertical variable")?;
+            vertical_dim = Some(
+                vertical_var
+                    .dimensions()
+                    .first()
+                    .ok_or("No vertical dimension")?
+                    .name(),
+            );
+            let variable = root.variable(&self.variable_name).ok_or("No variable found")?;
+            if !variable.dimensions().iter().any(|d| Some(d.name()) == vertical_dim) {
+                return Err(format!("{} is not indexed by the vertical dimension", self.variable_name));
+            }
+            levels = vec![0.; vertical_var.len()];
+            vertical_var
+                .values_to(levels.as_mut_slice(), None, None)
+                .map_err(format_error)?;
+        }
+        let level_count = levels.len().max(1);
+
+        let samples = sample_path(path, spacing);
+        let mut transect = Transect {
+            distances: Vec::with_capacity(samples.len()),
+            lons: Vec::with_capacity(samples.len()),
+            lats: Vec::with_capacity(samples.len()),
+            levels,
+            values: Vec::with_capacity(samples.len()),
+        };
+        // indices of the last grid point read, consecutive samples
+        // often fall into the same cell
+        let mut last_indices: Option<(usize, usize)> = None;
+        for (distance, lon, lat) in samples {
+            transect.distances.push(distance);
+            transect.lons.push(lon);
+            transect.lats.push(lat);
+            let indices = match self.closest_indices(lat, lon) {
+                Ok(indices) => indices,
+                Err(_) => {
+                    last_indices = None;
+                    transect.values.push(vec![f32::NAN; level_count]);
+                    continue;
+                }
+            };
+            if last_indices == Some(indices) {
+                let previous = transect.values[transect.values.len() - 1].clone();
+                transect.values.push(previous);
+                continue;
+            }
+            let (lat_idx, lon_idx) = indices;
+            let mut windows = vec![
+                (self.lat_dim.as_str(), lat_idx, 1),
+                (self.lon_dim.as_str(), lon_idx, 1),
+            ];
+            if let Some(ref dim) = vertical_dim {
+                windows.push((dim.as_str(), 0, level_count));
+            }
+            transect
+                .values
+                .push(self.read_values(&self.variable_name, &windows)?);
+            last_indices = Some(indices);
+        }
+        Ok(transect)
+    }
+
+    /// Computes the statistics of the dataset cells whose center lies inside each zone
+    /// (eg: the mean rainfall per district).
+    ///
+    /// Only the bounding box of each zone is read from the netCDF file.
+    /// The mean and percentiles are weighted by the area of each cell.
+    ///
+    /// #Args
+    ///  * `zones` polygons, see `Zone::from_geojson`
+    ///  * `percentiles` the percentiles to compute, in [0; 100]
+    pub fn zonal_statistics(
+        &self,
+        zones: &[Zone],
+        percentiles: &[f32],
+    ) -> Result<Vec<ZonalStatistics>, String> {
+        // cell centers and edges, in WGS84
+        let lons: Vec<f64> = self.lon.iter().map(|x| lon_meters_to_wgs84(*x)).collect();
+        let lats: Vec<f64> = self.lat.iter().map(|y| lat_meters_to_wgs84(*y)).collect();
+        let lon_edges: Vec<f64> = self.lon_edges.iter().map(|x| lon_meters_to_wgs84(*x)).collect();
+        let lat_edges: Vec<f64> = self.lat_edges.iter().map(|y| lat_meters_to_wgs84(*y)).collect();
+
+        let mut statistics: Vec<ZonalStatistics> = Vec::with_capacity(zones.len());
+        for zone in zones {
+            let mut values: Vec<(f32, f64)> = Vec::new();
+            if let Some(bounds) = zone.bounds() {
+                let bbox = bounds.xy();
+                if self.contains_bbox(&bbox) {
+                    let (i_lat_min, i_lat_max, i_lon_min, i_lon_max) = self.window_indices(&bbox)?;
+                    let lon_count = i_lon_max - i_lon_min + 1;
+                    let window = self.read_values(
+                        &self.variable_name,
+                        &[
+                            (&self.lat_dim, i_lat_min, i_lat_max - i_lat_min + 1),
+                            (&self.lon_dim, i_lon_min, lon_count),
+                        ],
+                    )?;
+                    for i_lat in i_lat_min..(i_lat_max + 1) {
+                        for i_lon in i_lon_min..(i_lon_max + 1) {
+                            let value = window[(i_lat - i_lat_min) * lon_count + i_lon - i_lon_min];
+                            if value.is_nan() || !zone.contains(lons[i_lon], lats[i_lat]) {
+                                continue;
+                            }
+                            let cell = LonLatBbox {
+                                west: lon_edges[i_lon],
+                                south: lat_edges[i_lat],
+                                east: lon_edges[i_lon + 1],
+                                north: lat_edges[i_lat + 1],
+                            };
+                            values.push((value, cell.area()));
+                        }
                     }
                 }
-                return Ok(value);
             }
+            statistics.push(ZonalStatistics::from_weighted_values(
+                zone.id.clone(),
+                values,
+                percentiles,
+            ));
         }
-        Err("Dataset error".into())
+        Ok(statistics)
+    }
+
+    /// Computes the statistics (min, max, mean, histogram and percentiles)
+    /// of the dataset variable, over the whole grid or inside a bounding box.
+    ///
+    /// Use `Scale::from_statistics` to build a color scale from it.
+    pub fn statistics(&self, bbox: Option<&LonLatBbox>) -> Result<Statistics, String> {
+        let (i_lat_min, i_lat_max, i_lon_min, i_lon_max) = match bbox {
+            Some(bbox) => {
+                let bbox = bbox.xy();
+                if !self.contains_bbox(&bbox) {
+                    return Err("Bounding box outside range".into());
+                }
+                self.window_indices(&bbox)?
+            }
+            None => (0, self.lat.len() - 1, 0, self.lon.len() - 1),
+        };
+        let values = self.read_values(
+            &self.variable_name,
+            &[
+                (&self.lat_dim, i_lat_min, i_lat_max - i_lat_min + 1),
+                (&self.lon_dim, i_lon_min, i_lon_max - i_lon_min + 1),
+            ],
+        )?;
+        Ok(Statistics::from_values(&values, 1000))
+    }
+
+    /// Lists the content of the netCDF file: dimensions, variables,
+    /// global attributes, geographic extent and grid resolution.
+    pub fn inspect(&self) -> Result<DatasetSummary, String> {
+        let root = self.file.root().ok_or("No root group !")?;
+        let dimensions: Vec<DimensionInfo> = root
+            .dimensions()
+            .map(|dimension| DimensionInfo {
+                name: dimension.name(),
+                len: dimension.len(),
+                unlimited: dimension.is_unlimited(),
+            })
+            .collect();
+        let variables: Vec<VariableInfo> = root
+            .variables()
+            .map(|variable| VariableInfo {
+                name: variable.name(),
+                dimensions: variable.dimensions().iter().map(|d| d.name()).collect(),
+                shape: variable.dimensions().iter().map(|d| d.len()).collect(),
+                data_type: variable.vartype().name(),
+                units: variable_string_attribute(&variable, "units"),
+                long_name: variable_string_attribute(&variable, "long_name"),
+                attributes: variable
+                    .attributes()
+                    .filter_map(|attr| {
+                        let name = attr.name().to_string();
+                        attr.value().ok().map(|value| (name, value.into()))
+                    })
+                    .collect(),
+            })
+            .collect();
+        let attributes = root
+            .attributes()
+            .filter_map(|attr| {
+                let name = attr.name().to_string();
+                attr.value().ok().map(|value| (name, value.into()))
+            })
+            .collect();
+
+        // mean spacing between two grid points, in degrees
+        let spacing = |first: f64, last: f64, len: usize| -> f64 {
+            if len < 2 {
+                0.
+            } else {
+                (last - first).abs() / (len - 1) as f64
+            }
+        };
+        let (west, east) = (lon_meters_to_wgs84(self.min_lon), lon_meters_to_wgs84(self.max_lon));
+        let (south, north) = (lat_meters_to_wgs84(self.min_lat), lat_meters_to_wgs84(self.max_lat));
+        Ok(DatasetSummary {
+            dimensions,
+            variables,
+            attributes,
+            extent: LonLatBbox { west, south, east, north },
+            resolution: (
+                spacing(west, east, self.lon.len()),
+                spacing(south, north, self.lat.len()),
+            ),
+        })
     }
 }
 
@@ -280,3 +994,104 @@ fn test_data_fetch() {
     let values = dataset.get_tile_data(&tile);
     assert!(&values.is_ok());
 }
+
+#[test]
+fn test_dataset_statistics() {
+    let dataset_path = "./examples_data/wind_magnitude_reduced.nc";
+    let dataset = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path).unwrap();
+    let statistics = dataset.statistics(None).unwrap();
+    assert!(statistics.count > 0);
+    assert!(statistics.min <= statistics.mean && statistics.mean <= statistics.max);
+}
+
+#[test]
+fn test_dataset_inspection() {
+    let dataset_path = "./examples_data/wind_magnitude_reduced.nc";
+    let dataset = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path).unwrap();
+    let summary = dataset.inspect().unwrap();
+    assert!(summary.variable("wind_magnitude").is_some());
+    assert!(summary.extent.west < summary.extent.east);
+}
+
+#[test]
+fn test_chunked_reads() {
+    let dataset_path = "./examples_data/wind_magnitude_reduced.nc";
+    let uncached = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path).unwrap();
+    // room for 4 chunks, so the reads also evict chunks
+    let cached = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path)
+        .unwrap()
+        .with_cache(4 * CHUNK_SIZE * CHUNK_SIZE * std::mem::size_of::<f32>());
+    let same = |a: &[f32], b: &[f32]| {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a == b || (a.is_nan() && b.is_nan()))
+    };
+    // (start, count) windows along an axis of `n` cells: the whole axis, a window
+    // straddling the first chunk boundary, and the partial chunk at the edge of the grid
+    let windows = |n: usize| {
+        let straddling = CHUNK_SIZE.saturating_sub(5).min(n - 1);
+        let edge = n.saturating_sub(7);
+        vec![(0, n), (straddling, 10.min(n - straddling)), (edge, n - edge)]
+    };
+    for (lat_start, lat_count) in windows(uncached.lat.len()) {
+        for (lon_start, lon_count) in windows(uncached.lon.len()) {
+            let window = [
+                (uncached.lat_dim.as_str(), lat_start, lat_count),
+                (uncached.lon_dim.as_str(), lon_start, lon_count),
+            ];
+            let expected = uncached.read_raw_values("wind_magnitude", &window).unwrap();
+            assert!(same(&cached.read_raw_values("wind_magnitude", &window).unwrap(), &expected));
+            // read again, from the cached chunks
+            assert!(same(&cached.read_raw_values("wind_magnitude", &window).unwrap(), &expected));
+        }
+    }
+    for tile in [Tile { x: 0, y: 0, z: 0 }, Tile { x: 15, y: 15, z: 9 }, Tile { x: 16, y: 15, z: 9 }] {
+        let expected = uncached.get_tile_data(&tile).unwrap();
+        assert!(same(&cached.get_tile_data(&tile).unwrap().values, &expected.values));
+    }
+}
+
+#[test]
+fn test_dataset_overviews() {
+    let dataset_path = "./examples_data/wind_magnitude_reduced.nc";
+    let plain = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path).unwrap();
+    let dataset = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path)
+        .unwrap()
+        .with_overviews(None, Aggregation::Mean)
+        .unwrap();
+    assert!(!dataset.overviews.is_empty());
+    let tile = Tile { x: 0, y: 0, z: 0 };
+    let bbox = tile.xy_bounds();
+    let full = plain.get_sized_tile_data(&tile, 0, TILE_SIZE).unwrap();
+    let mut previous_cells = 0;
+    for &size in &[64, 256, 1024] {
+        let data = dataset.get_sized_tile_data(&tile, 0, size).unwrap();
+        let cells = data.lat.len() * data.lon.len();
+        // either the grid itself, or an overview whose cells are smaller than half a pixel
+        if cells < full.lat.len() * full.lon.len() {
+            let width = (data.lon_edges[data.lon.len()] - data.lon_edges[0]) / data.lon.len() as f64;
+            assert!(width <= (bbox.east - bbox.west) / (2 * size) as f64);
+        } else {
+            assert_eq!(data.values.len(), full.values.len());
+        }
+        // larger tiles never get a coarser overview
+        assert!(cells >= previous_cells);
+        previous_cells = cells;
+    }
+
+    // the overviews written to a sidecar are loaded back
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let sidecar = std::env::temp_dir().join(format!("tiler_test_dataset_{}_{}.ovr", std::process::id(), nanos));
+    let sidecar = sidecar.to_str().unwrap();
+    let built = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path)
+        .unwrap()
+        .with_overviews(Some(sidecar), Aggregation::Mean)
+        .unwrap();
+    let loaded = Dataset::new("latitude", "longitude", "wind_magnitude", dataset_path)
+        .unwrap()
+        .with_overviews(Some(sidecar), Aggregation::Mean)
+        .unwrap();
+    let _ = fs::remove_file(sidecar);
+    assert_eq!(built.overviews, loaded.overviews);
+    assert_eq!(built.overviews, dataset.overviews);
+}
@@ -0,0 +1,148 @@
+use std::f32;
+
+/// Smoothing filters, applied on the regridded tile values.
+///
+/// Every filter ignores missing (NaN) values, and keeps them missing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Gaussian blur, `sigma` being expressed in pixels
+    /// (a `sigma` which is not strictly positive leaves the values untouched)
+    Gaussian { sigma: f32 },
+    /// Median of the (2 * radius + 1)² pixels window
+    Median { radius: usize },
+    /// Mean of the (2 * radius + 1)² pixels window
+    Box { radius: usize },
+}
+
+impl Filter {
+    /// Returns the number of neighbouring pixels needed on each side of a pixel
+    pub fn radius(&self) -> usize {
+        match *self {
+            Filter::Gaussian { sigma } if sigma > 0. => (3. * sigma).ceil().max(1.) as usize,
+            Filter::Gaussian { .. } => 0,
+            Filter::Median { radius } | Filter::Box { radius } => radius,
+        }
+    }
+
+    /// Returns the same filter for a grid `factor` times finer (eg: @2x tiles)
+    pub fn scaled(&self, factor: usize) -> Filter {
+        match *self {
+            Filter::Gaussian { sigma } => Filter::Gaussian { sigma: sigma * factor as f32 },
+            Filter::Median { radius } => Filter::Median { radius: radius * factor },
+            Filter::Box { radius } => Filter::Box { radius: radius * factor },
+        }
+    }
+
+    /// Returns the 1-D kernel of the separable filters
+    fn kernel(&self) -> Vec<f32> {
+        let radius = self.radius() as isize;
+        match *self {
+            Filter::Gaussian { sigma } if radius > 0 => (-radius..(radius + 1))
+                .map(|x| (-((x * x) as f32) / (2. * sigma * sigma)).exp())
+                .collect(),
+            _ => vec![1.; 2 * radius as usize + 1],
+        }
+    }
+}
+
+/// Convolves each row (if `horizontal`) or column of a flattened grid with `kernel`
+fn convolve(values: &[f32], width: usize, height: usize, kernel: &[f32], horizontal: bool) -> Vec<f32> {
+    let radius = (kernel.len() / 2) as isize;
+    let mut output = vec![0.; values.len()];
+    for row in 0..height {
+        for col in 0..width {
+            let mut sum: f32 = 0.;
+            for (k, weight) in kernel.iter().enumerate() {
+                let offset = k as isize - radius;
+                let (r, c) = if horizontal {
+                    (row as isize, col as isize + offset)
+                } else {
+                    (row as isize + offset, col as isize)
+                };
+                if r < 0 || c < 0 || r >= height as isize || c >= width as isize {
+                    continue;
+                }
+                sum += weight * values[r as usize * width + c as usize];
+            }
+            output[row * width + col] = sum;
+        }
+    }
+    output
+}
+
+/// Median of the valid values of each (2 * radius + 1)² window
+fn median(values: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let r = radius as isize;
+    let mut output = vec![f32::NAN; values.len()];
+    let mut window: Vec<f32> = Vec::with_capacity((2 * radius + 1) * (2 * radius + 1));
+    for row in 0..height as isize {
+        for col in 0..width as isize {
+            window.clear();
+            for nr in (row - r).max(0)..(row + r + 1).min(height as isize) {
+                for nc in (col - r).max(0)..(col + r + 1).min(width as isize) {
+                    let value = values[nr as usize * width + nc as usize];
+                    if !value.is_nan() {
+                        window.push(value);
+                    }
+                }
+            }
+            if !window.is_empty() {
+                window.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                output[row as usize * width + col as usize] = window[window.len() / 2];
+            }
+        }
+    }
+    output
+}
+
+/// Applies `filter` on a flattened (row major) `width` x `height` grid of values
+pub fn apply_filter(values: &[f32], width: usize, height: usize, filter: &Filter) -> Vec<f32> {
+    let mut output = match *filter {
+        Filter::Median { radius } => median(values, width, height, radius),
+        _ => {
+            // normalized convolution: convolve the valid values and the validity mask,
+            // so missing values don't darken their neighbours
+            let kernel = filter.kernel();
+            let masked: Vec<f32> = values.iter().map(|v| if v.is_nan() { 0. } else { *v }).collect();
+            let mask: Vec<f32> = values.iter().map(|v| if v.is_nan() { 0. } else { 1. }).collect();
+            let sums = convolve(&convolve(&masked, width, height, &kernel, true), width, height, &kernel, false);
+            let weights = convolve(&convolve(&mask, width, height, &kernel, true), width, height, &kernel, false);
+            sums.iter().zip(weights.iter()).map(|(s, w)| s / w).collect()
+        }
+    };
+    // keep missing values missing
+    for (out, value) in output.iter_mut().zip(values.iter()) {
+        if value.is_nan() {
+            *out = f32::NAN;
+        }
+    }
+    output
+}
+
+#[test]
+fn test_filters() {
+    let nan = f32::NAN;
+    let values = vec![
+        0., 0., 0.,
+        0., 9., 0.,
+        0., 0., nan,
+    ];
+    let boxed = apply_filter(&values, 3, 3, &Filter::Box { radius: 1 });
+    assert_eq!(boxed[4], 9. / 8.);
+    assert_eq!(boxed[0], 9. / 4.);
+    assert!(boxed[8].is_nan());
+
+    let median = apply_filter(&values, 3, 3, &Filter::Median { radius: 1 });
+    assert_eq!(median[4], 0.);
+
+    let blurred = apply_filter(&values, 3, 3, &Filter::Gaussian { sigma: 0.5 });
+    assert!(blurred[4] < 9. && blurred[4] > blurred[1] && blurred[1] > 0.);
+    assert_eq!(Filter::Gaussian { sigma: 0.5 }.radius(), 2);
+
+    // no blur
+    for sigma in &[0., -1., f32::NAN] {
+        let filter = Filter::Gaussian { sigma: *sigma }.scaled(2);
+        assert_eq!(filter.radius(), 0);
+        assert_eq!(apply_filter(&values, 3, 3, &filter)[4], 9.);
+    }
+}
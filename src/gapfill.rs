@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::f32;
+
+/// Describes how missing (NaN) cells are estimated from their valid neighbours
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GapFillMethod {
+    /// Copy the value of the nearest valid cell
+    NearestValid,
+    /// Inverse distance weighting of the valid cells within `radius` cells,
+    /// weights being `1 / distance ^ power`
+    InverseDistance { radius: usize, power: f32 },
+    /// Smoothly interpolate the gap from its border, by iteratively
+    /// replacing each missing cell by the mean of its 4 neighbours
+    Laplacian { iterations: usize },
+}
+
+/// Describes a gap filling stage, applied on `TileData` before regridding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GapFill {
+    pub method: GapFillMethod,
+    /// Gaps (groups of connected missing cells) larger than
+    /// `max_gap_size` cells are left untouched.
+    pub max_gap_size: usize,
+}
+
+impl GapFill {
+    /// Number of cells needed around a window so its gaps are measured (and filled)
+    /// as on the whole grid: a gap reaching the window is either entirely inside
+    /// this margin, or has more than `max_gap_size` cells in it.
+    pub fn margin(&self) -> usize {
+        let radius = match self.method {
+            GapFillMethod::InverseDistance { radius, .. } => radius,
+            _ => 0,
+        };
+        self.max_gap_size + 1 + radius
+    }
+}
+
+/// Returns a mask of the missing cells which belong to a gap of at most `max_gap_size` cells
+fn fillable_cells(values: &[f32], width: usize, height: usize, max_gap_size: usize) -> Vec<bool> {
+    let mut fillable = vec![false; values.len()];
+    let mut visited = vec![false; values.len()];
+    let mut gap: Vec<usize> = Vec::new();
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    for start in 0..values.len() {
+        if visited[start] || !values[start].is_nan() {
+            continue;
+        }
+        // collect the connected missing cells (4-connectivity)
+        gap.clear();
+        visited[start] = true;
+        queue.push_back(start);
+        while let Some(idx) = queue.pop_front() {
+            gap.push(idx);
+            let (row, col) = (idx / width, idx % width);
+            let mut neighbours: Vec<usize> = Vec::with_capacity(4);
+            if row > 0 { neighbours.push(idx - width); }
+            if row + 1 < height { neighbours.push(idx + width); }
+            if col > 0 { neighbours.push(idx - 1); }
+            if col + 1 < width { neighbours.push(idx + 1); }
+            for n in neighbours {
+                if !visited[n] && values[n].is_nan() {
+                    visited[n] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+        if gap.len() <= max_gap_size {
+            for idx in &gap {
+                fillable[*idx] = true;
+            }
+        }
+    }
+    fillable
+}
+
+/// Copy into each fillable cell the value of the closest valid cell (chessboard distance)
+fn fill_nearest(values: &mut [f32], fillable: &[bool], width: usize, height: usize) {
+    // breadth first search, starting from every valid cell
+    let mut queue: VecDeque<usize> = (0..values.len()).filter(|i| !values[*i].is_nan()).collect();
+    while let Some(idx) = queue.pop_front() {
+        let (row, col) = ((idx / width) as isize, (idx % width) as isize);
+        for d_row in -1..2 {
+            for d_col in -1..2 {
+                let (r, c) = (row + d_row, col + d_col);
+                if r < 0 || c < 0 || r >= height as isize || c >= width as isize {
+                    continue;
+                }
+                let n = r as usize * width + c as usize;
+                if fillable[n] && values[n].is_nan() {
+                    values[n] = values[idx];
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+}
+
+/// Inverse distance weighting of the valid cells within `radius` cells
+fn fill_inverse_distance(
+    values: &mut [f32],
+    fillable: &[bool],
+    width: usize,
+    height: usize,
+    radius: usize,
+    power: f32,
+) {
+    let source = values.to_vec();
+    let r = radius as isize;
+    for idx in (0..values.len()).filter(|i| fillable[*i]) {
+        let (row, col) = ((idx / width) as isize, (idx % width) as isize);
+        let mut sum: f32 = 0.;
+        let mut weights: f32 = 0.;
+        for d_row in -r..(r + 1) {
+            for d_col in -r..(r + 1) {
+                let (nr, nc) = (row + d_row, col + d_col);
+                if nr < 0 || nc < 0 || nr >= height as isize || nc >= width as isize {
+                    continue;
+                }
+                let value = source[nr as usize * width + nc as usize];
+                let distance = ((d_row * d_row + d_col * d_col) as f32).sqrt();
+                if value.is_nan() || distance > radius as f32 {
+                    continue;
+                }
+                let weight = 1. / distance.powf(power);
+                sum += weight * value;
+                weights += weight;
+            }
+        }
+        if weights > 0. {
+            values[idx] = sum / weights;
+        }
+    }
+}
+
+/// Solve the Laplace equation inside the fillable cells (Gauss-Seidel iterations),
+/// using the valid cells as boundary conditions.
+fn fill_laplacian(values: &mut [f32], fillable: &[bool], width: usize, height: usize, iterations: usize) {
+    // start from the nearest valid values, it speeds up the convergence
+    fill_nearest(values, fillable, width, height);
+    for _ in 0..iterations {
+        for idx in (0..values.len()).filter(|i| fillable[*i]) {
+            let (row, col) = (idx / width, idx % width);
+            let mut neighbours: Vec<usize> = Vec::with_capacity(4);
+            if row > 0 { neighbours.push(idx - width); }
+            if row + 1 < height { neighbours.push(idx + width); }
+            if col > 0 { neighbours.push(idx - 1); }
+            if col + 1 < width { neighbours.push(idx + 1); }
+            let mut sum: f32 = 0.;
+            let mut count: f32 = 0.;
+            for n in neighbours {
+                if !values[n].is_nan() {
+                    sum += values[n];
+                    count += 1.;
+                }
+            }
+            if count > 0. {
+                values[idx] = sum / count;
+            }
+        }
+    }
+}
+
+/// Fill the gaps of a flattened (row major) `width` x `height` grid of values
+pub fn fill_gaps(values: &mut [f32], width: usize, height: usize, gap_fill: &GapFill) {
+    if values.len() != width * height || gap_fill.max_gap_size == 0 {
+        return;
+    }
+    let fillable = fillable_cells(values, width, height, gap_fill.max_gap_size);
+    if !fillable.iter().any(|f| *f) {
+        return;
+    }
+    match gap_fill.method {
+        GapFillMethod::NearestValid => fill_nearest(values, &fillable, width, height),
+        GapFillMethod::InverseDistance { radius, power } => {
+            fill_inverse_distance(values, &fillable, width, height, radius, power)
+        }
+        GapFillMethod::Laplacian { iterations } => {
+            fill_laplacian(values, &fillable, width, height, iterations)
+        }
+    }
+}
+
+#[test]
+fn test_gap_size_cap() {
+    let nan = f32::NAN;
+    // a single cell hole, and a 3 cells hole on the last row
+    let mut values = vec![
+        1., 1., 1.,
+        1., nan, 1.,
+        1., 1., 1.,
+        nan, nan, nan,
+    ];
+    let gap_fill = GapFill { method: GapFillMethod::NearestValid, max_gap_size: 2 };
+    fill_gaps(&mut values, 3, 4, &gap_fill);
+    assert_eq!(values[4], 1.);
+    assert!(values[9..].iter().all(|v| v.is_nan()));
+}
+
+#[test]
+fn test_gap_fill_methods() {
+    let nan = f32::NAN;
+    let grid = vec![
+        0., 0., 0.,
+        2., nan, 2.,
+        4., 4., 4.,
+    ];
+    let methods = [
+        GapFillMethod::InverseDistance { radius: 1, power: 2. },
+        GapFillMethod::Laplacian { iterations: 10 },
+    ];
+    for method in methods.iter() {
+        let mut values = grid.clone();
+        fill_gaps(&mut values, 3, 3, &GapFill { method: *method, max_gap_size: 4 });
+        assert!((values[4] - 2.).abs() < 1e-6);
+    }
+    // too far from any valid cell
+    let mut values = vec![nan, nan, nan, 1.];
+    let method = GapFillMethod::InverseDistance { radius: 1, power: 1. };
+    fill_gaps(&mut values, 4, 1, &GapFill { method, max_gap_size: 4 });
+    assert!(values[0].is_nan() && values[1].is_nan() && values[2] == 1.);
+}
+
+#[test]
+fn test_gap_fill_margin() {
+    let nan = f32::NAN;
+    // a 3 cells gap (too large), crossing the border of the window made of the first 2 columns
+    let row = [1., nan, nan, nan, 1., 1., 1., 1.];
+    let gap_fill = GapFill { method: GapFillMethod::NearestValid, max_gap_size: 2 };
+    let mut whole = row.to_vec();
+    fill_gaps(&mut whole, 8, 1, &gap_fill);
+    // clipped to the window, the gap looks small enough
+    let mut clipped = row[..2].to_vec();
+    fill_gaps(&mut clipped, 2, 1, &gap_fill);
+    assert_eq!(clipped[1], 1.);
+    // extended by the margin, the window agrees with the whole grid
+    let mut extended = row[..(2 + gap_fill.margin())].to_vec();
+    fill_gaps(&mut extended, 2 + gap_fill.margin(), 1, &gap_fill);
+    assert!(extended[1].is_nan() && whole[1].is_nan());
+}
@@ -0,0 +1,210 @@
+use json::{self, JsonValue};
+use tile::LonLatBbox;
+
+/// A polygon, expressed in WGS 84 coordinates (lon, lat).
+///
+/// The first ring is the exterior boundary, the following ones are holes.
+#[derive(Debug, PartialEq)]
+pub struct Polygon {
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+impl Polygon {
+    /// Returns true if the point (lon, lat) lies inside the polygon,
+    /// (even-odd rule, so points inside a hole are excluded).
+    pub fn contains(&self, lon: f64, lat: f64) -> bool {
+        let mut inside = false;
+        for ring in &self.rings {
+            if ring.is_empty() {
+                continue;
+            }
+            let mut j = ring.len() - 1;
+            for i in 0..ring.len() {
+                let (xi, yi) = ring[i];
+                let (xj, yj) = ring[j];
+                if (yi > lat) != (yj > lat) && lon < (xj - xi) * (lat - yi) / (yj - yi) + xi {
+                    inside = !inside;
+                }
+                j = i;
+            }
+        }
+        inside
+    }
+
+    /// Returns the bounding box of the exterior ring
+    pub fn bounds(&self) -> Option<LonLatBbox> {
+        let exterior = self.rings.first()?;
+        if exterior.is_empty() {
+            return None;
+        }
+        let mut bbox = LonLatBbox {
+            west: exterior[0].0,
+            south: exterior[0].1,
+            east: exterior[0].0,
+            north: exterior[0].1,
+        };
+        for &(lon, lat) in exterior {
+            bbox.west = bbox.west.min(lon);
+            bbox.east = bbox.east.max(lon);
+            bbox.south = bbox.south.min(lat);
+            bbox.north = bbox.north.max(lat);
+        }
+        Some(bbox)
+    }
+}
+
+/// A set of polygons read from a single GeoJSON feature (or geometry).
+#[derive(Debug, PartialEq)]
+pub struct Zone {
+    /// The feature `id`, or its `name` property
+    pub id: Option<String>,
+    pub polygons: Vec<Polygon>,
+}
+
+impl Zone {
+    /// Returns true if the point (lon, lat) lies inside one of the polygons
+    pub fn contains(&self, lon: f64, lat: f64) -> bool {
+        self.polygons.iter().any(|polygon| polygon.contains(lon, lat))
+    }
+
+    /// Returns the bounding box of all the polygons
+    pub fn bounds(&self) -> Option<LonLatBbox> {
+        let mut bounds: Option<LonLatBbox> = None;
+        for bbox in self.polygons.iter().filter_map(Polygon::bounds) {
+            bounds = Some(match bounds {
+                None => bbox,
+                Some(b) => LonLatBbox {
+                    west: b.west.min(bbox.west),
+                    south: b.south.min(bbox.south),
+                    east: b.east.max(bbox.east),
+                    north: b.north.max(bbox.north),
+                },
+            });
+        }
+        bounds
+    }
+
+    /// Parses the Polygons and MultiPolygons of a GeoJSON document
+    /// (a FeatureCollection, a Feature or a bare geometry),
+    /// other geometries are ignored.
+    ///
+    /// Returns one `Zone` per feature.
+    pub fn from_geojson(text: &str) -> Result<Vec<Zone>, String> {
+        let document = json::parse(text)?;
+        let mut zones: Vec<Zone> = Vec::new();
+        match document.get("type").and_then(JsonValue::as_str) {
+            Some("FeatureCollection") => {
+                let features = document
+                    .get("features")
+                    .and_then(JsonValue::as_array)
+                    .ok_or("FeatureCollection without features")?;
+                for feature in features {
+                    if let Some(zone) = parse_feature(feature)? {
+                        zones.push(zone);
+                    }
+                }
+            }
+            Some("Feature") => {
+                if let Some(zone) = parse_feature(&document)? {
+                    zones.push(zone);
+                }
+            }
+            Some(_) => {
+                let polygons = parse_geometry(&document)?;
+                if !polygons.is_empty() {
+                    zones.push(Zone { id: None, polygons });
+                }
+            }
+            None => return Err("Not a GeoJSON object".into()),
+        }
+        Ok(zones)
+    }
+}
+
+fn parse_feature(feature: &JsonValue) -> Result<Option<Zone>, String> {
+    let polygons = match feature.get("geometry") {
+        Some(geometry) => parse_geometry(geometry)?,
+        None => Vec::new(),
+    };
+    if polygons.is_empty() {
+        return Ok(None);
+    }
+    let id = match feature.get("id") {
+        Some(JsonValue::String(id)) => Some(id.clone()),
+        Some(JsonValue::Number(id)) => Some(id.to_string()),
+        _ => feature
+            .get("properties")
+            .and_then(|properties| properties.get("name"))
+            .and_then(JsonValue::as_str)
+            .map(|name| name.to_string()),
+    };
+    Ok(Some(Zone { id, polygons }))
+}
+
+/// Parses a Polygon or MultiPolygon geometry (or a GeometryCollection of them)
+fn parse_geometry(geometry: &JsonValue) -> Result<Vec<Polygon>, String> {
+    let coordinates = geometry.get("coordinates");
+    match geometry.get("type").and_then(JsonValue::as_str) {
+        Some("Polygon") => Ok(vec![parse_polygon(
+            coordinates.ok_or("Polygon without coordinates")?,
+        )?]),
+        Some("MultiPolygon") => coordinates
+            .and_then(JsonValue::as_array)
+            .ok_or("MultiPolygon without coordinates")?
+            .iter()
+            .map(parse_polygon)
+            .collect(),
+        Some("GeometryCollection") => {
+            let mut polygons: Vec<Polygon> = Vec::new();
+            for member in geometry
+                .get("geometries")
+                .and_then(JsonValue::as_array)
+                .ok_or("GeometryCollection without geometries")?
+            {
+                polygons.extend(parse_geometry(member)?);
+            }
+            Ok(polygons)
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn parse_polygon(coordinates: &JsonValue) -> Result<Polygon, String> {
+    let mut rings: Vec<Vec<(f64, f64)>> = Vec::new();
+    for ring in coordinates.as_array().ok_or("Invalid polygon coordinates")? {
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for position in ring.as_array().ok_or("Invalid polygon ring")? {
+            let position = position.as_array().ok_or("Invalid position")?;
+            if position.len() < 2 {
+                return Err("Invalid position".into());
+            }
+            points.push((
+                position[0].as_f64().ok_or("Invalid longitude")?,
+                position[1].as_f64().ok_or("Invalid latitude")?,
+            ));
+        }
+        rings.push(points);
+    }
+    Ok(Polygon { rings })
+}
+
+#[test]
+fn test_geojson_parsing() {
+    let zones = Zone::from_geojson(
+        r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"name": "square"}, "geometry": {
+                "type": "Polygon",
+                "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]],
+                                [[4, 4], [6, 4], [6, 6], [4, 6], [4, 4]]]}},
+            {"type": "Feature", "id": 2, "properties": null, "geometry": {
+                "type": "Point", "coordinates": [0, 0]}}
+        ]}"#,
+    ).unwrap();
+    assert_eq!(zones.len(), 1);
+    assert_eq!(zones[0].id, Some("square".into()));
+    assert!(zones[0].contains(2., 2.));
+    assert!(!zones[0].contains(5., 5.));
+    assert!(!zones[0].contains(12., 2.));
+    let bounds = zones[0].bounds().unwrap();
+    assert_eq!((bounds.west, bounds.north), (0., 10.));
+}
@@ -0,0 +1,294 @@
+//! Minimal helpers to read and write JSON documents by hand.
+
+use std::fmt::Display;
+use std::str::Chars;
+use std::iter::Peekable;
+
+/// Deepest nesting of arrays and objects accepted by `parse`,
+/// so malformed documents can't overflow the stack
+const MAX_DEPTH: usize = 128;
+
+/// A parsed JSON value
+#[derive(Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Members are kept in the document order
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Returns the member `key` of an object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match *self {
+            JsonValue::Object(ref members) => members
+                .iter()
+                .find(|member| member.0 == key)
+                .map(|member| &member.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            JsonValue::Number(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            JsonValue::String(ref x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match *self {
+            JsonValue::Array(ref x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a JSON document
+pub fn parse(text: &str) -> Result<JsonValue, String> {
+    let mut chars = text.chars().peekable();
+    let value = parse_value(&mut chars, 0)?;
+    skip_whitespaces(&mut chars);
+    if chars.peek().is_some() {
+        return Err("Trailing characters after the JSON document".into());
+    }
+    Ok(value)
+}
+
+fn skip_whitespaces(chars: &mut Peekable<Chars>) {
+    while let Some(&c) = chars.peek() {
+        if !c.is_whitespace() {
+            break;
+        }
+        chars.next();
+    }
+}
+
+/// Consumes `expected` or fails
+fn expect_literal(chars: &mut Peekable<Chars>, expected: &str) -> Result<(), String> {
+    for c in expected.chars() {
+        if chars.next() != Some(c) {
+            return Err(format!("Invalid JSON literal, expected {}", expected));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a value nested in `depth` arrays or objects
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Result<JsonValue, String> {
+    skip_whitespaces(chars);
+    match chars.peek().cloned() {
+        Some('{') | Some('[') if depth >= MAX_DEPTH => {
+            Err(format!("JSON nested deeper than {} levels", MAX_DEPTH))
+        }
+        Some('{') => parse_object(chars, depth + 1),
+        Some('[') => parse_array(chars, depth + 1),
+        Some('"') => Ok(JsonValue::String(parse_string(chars)?)),
+        Some('t') => expect_literal(chars, "true").map(|_| JsonValue::Bool(true)),
+        Some('f') => expect_literal(chars, "false").map(|_| JsonValue::Bool(false)),
+        Some('n') => expect_literal(chars, "null").map(|_| JsonValue::Null),
+        Some(c) if c == '-' || c.is_ascii_digit() => parse_number(chars),
+        Some(c) => Err(format!("Unexpected character in JSON: {}", c)),
+        None => Err("Unexpected end of JSON".into()),
+    }
+}
+
+fn parse_object(chars: &mut Peekable<Chars>, depth: usize) -> Result<JsonValue, String> {
+    chars.next(); // '{'
+    let mut members: Vec<(String, JsonValue)> = Vec::new();
+    skip_whitespaces(chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return Ok(JsonValue::Object(members));
+    }
+    loop {
+        skip_whitespaces(chars);
+        if chars.peek() != Some(&'"') {
+            return Err("Expected a key in JSON object".into());
+        }
+        let key = parse_string(chars)?;
+        skip_whitespaces(chars);
+        if chars.next() != Some(':') {
+            return Err("Expected ':' in JSON object".into());
+        }
+        members.push((key, parse_value(chars, depth)?));
+        skip_whitespaces(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(JsonValue::Object(members)),
+            _ => return Err("Expected ',' or '}' in JSON object".into()),
+        }
+    }
+}
+
+fn parse_array(chars: &mut Peekable<Chars>, depth: usize) -> Result<JsonValue, String> {
+    chars.next(); // '['
+    let mut items: Vec<JsonValue> = Vec::new();
+    skip_whitespaces(chars);
+    if chars.peek() == Some(&']') {
+        chars.next();
+        return Ok(JsonValue::Array(items));
+    }
+    loop {
+        items.push(parse_value(chars, depth)?);
+        skip_whitespaces(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => return Ok(JsonValue::Array(items)),
+            _ => return Err("Expected ',' or ']' in JSON array".into()),
+        }
+    }
+}
+
+/// Reads the 4 hexadecimal digits of a `\u` escape sequence
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let mut code: u32 = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|c| c.to_digit(16))
+            .ok_or("Invalid unicode escape in JSON string")?;
+        code = code * 16 + digit;
+    }
+    Ok(code)
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    chars.next(); // '"'
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(value),
+            Some('\\') => match chars.next() {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('/') => value.push('/'),
+                Some('b') => value.push('\u{8}'),
+                Some('f') => value.push('\u{c}'),
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('u') => {
+                    let mut code = parse_hex4(chars)?;
+                    // combine UTF-16 surrogate pairs
+                    if (0xD800..0xDC00).contains(&code) {
+                        expect_literal(chars, "\\u")?;
+                        let low = parse_hex4(chars)?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err("Invalid surrogate pair in JSON string".into());
+                        }
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                    } else if (0xDC00..0xE000).contains(&code) {
+                        return Err("Unpaired surrogate in JSON string".into());
+                    }
+                    value.push(::std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                }
+                _ => return Err("Invalid escape sequence in JSON string".into()),
+            },
+            Some(c) => value.push(c),
+            None => return Err("Unterminated JSON string".into()),
+        }
+    }
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<JsonValue, String> {
+    let mut number = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+            number.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    number
+        .parse::<f64>()
+        .map(JsonValue::Number)
+        .map_err(|_| format!("Invalid JSON number: {}", number))
+}
+
+/// Escapes a string and wraps it into double quotes.
+pub fn string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Formats a number, JSON has no representation for NaN
+/// and infinite values, so they are written as `null`.
+pub fn number<T: Into<f64> + Display + Copy>(value: T) -> String {
+    if value.into().is_finite() {
+        format!("{}", value)
+    } else {
+        "null".into()
+    }
+}
+
+/// Formats a slice of numbers as a JSON array.
+pub fn numbers<T: Into<f64> + Display + Copy>(values: &[T]) -> String {
+    let items: Vec<String> = values.iter().map(|v| number(*v)).collect();
+    format!("[{}]", items.join(","))
+}
+
+#[test]
+fn test_json_formatting() {
+    assert_eq!(string("wind \"speed\""), "\"wind \\\"speed\\\"\"");
+    assert_eq!(number(1.5_f32), "1.5");
+    assert_eq!(number(::std::f32::NAN), "null");
+    assert_eq!(numbers(&[1_f64, ::std::f64::INFINITY]), "[1,null]");
+}
+
+#[test]
+fn test_json_parsing() {
+    let value = parse(r#" {"type": "Point", "coordinates": [2.5, -4e1], "id": null,
+        "name": "L\u00e9on \"A\"", "valid": true} "#).unwrap();
+    assert_eq!(value.get("type").and_then(JsonValue::as_str), Some("Point"));
+    let coordinates = value.get("coordinates").and_then(JsonValue::as_array).unwrap();
+    assert_eq!(coordinates[1].as_f64(), Some(-40.));
+    assert_eq!(value.get("id"), Some(&JsonValue::Null));
+    assert_eq!(value.get("name").and_then(JsonValue::as_str), Some("L\u{e9}on \"A\""));
+    assert_eq!(value.get("valid"), Some(&JsonValue::Bool(true)));
+    assert!(parse("[1, 2").is_err());
+    assert!(parse("{} 3").is_err());
+}
+
+#[test]
+fn test_json_surrogates() {
+    let value = parse(r#""\ud83c\udf0a""#).unwrap();
+    assert_eq!(value.as_str(), Some("\u{1F30A}"));
+    // high surrogate followed by a non low surrogate, lone low surrogate
+    assert!(parse(r#""\ud83c\u0041""#).is_err());
+    assert!(parse(r#""\udf0a""#).is_err());
+}
+
+#[test]
+fn test_json_depth() {
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    assert!(parse(&nested(MAX_DEPTH)).is_ok());
+    assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+    assert!(parse(&format!("{}{}", "{\"a\":".repeat(MAX_DEPTH + 1), "1")).is_err());
+    // would overflow the stack without the depth limit
+    assert!(parse(&"[".repeat(500_000)).is_err());
+}
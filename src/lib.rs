@@ -55,10 +55,51 @@ mod tiledata;
 mod dataset;
 mod renderer;
 mod utils;
-pub use tiledata::TileData;
+mod json;
+mod timeseries;
+mod transect;
+mod geojson;
+mod stats;
+mod summary;
+mod units;
+mod gapfill;
+mod filter;
+mod source;
+mod points;
+mod mesh;
+mod mask;
+mod classes;
+mod composite;
+mod bivariate;
+mod alpha;
+mod resampling;
+mod aggregation;
+mod cache;
+mod overview;
+mod pipeline;
+pub use tiledata::{TileData,TileGrid,TILE_SIZE};
 pub use renderer::{Renderer,ImgTile};
+pub use pipeline::{MAX_METATILE_SIZE,MAX_SCALE_FACTOR,MAX_TILE_SIZE};
 pub use dataset::Dataset;
 pub use colormap::{ColorMap,CustomColormap};
-pub use tile::Tile;
+pub use tile::{Tile,LonLatBbox};
+pub use timeseries::TimeSeries;
+pub use transect::Transect;
+pub use geojson::{Zone,Polygon};
+pub use stats::{Histogram,Statistics,ZonalStatistics};
+pub use summary::{AttributeValue,DatasetSummary,DimensionInfo,VariableInfo};
 pub use scale::*;
+pub use gapfill::{GapFill,GapFillMethod};
+pub use filter::Filter;
+pub use source::DataSource;
+pub use points::{PointData,PointInterpolation};
+pub use mesh::MeshDataset;
+pub use mask::Mask;
+pub use classes::{Class,Classification};
+pub use composite::{Band,CompositeRenderer};
+pub use bivariate::{BivariateColorTable,BivariateRenderer};
+pub use alpha::AlphaRamp;
+pub use resampling::Resampling;
+pub use aggregation::Aggregation;
+pub use cache::CHUNK_SIZE;
 
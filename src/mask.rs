@@ -0,0 +1,96 @@
+use std::f32;
+use geojson::Zone;
+use source::DataSource;
+use resampling::Resampling;
+use aggregation::Aggregation;
+use tile::{lat_meters_to_wgs84, lon_meters_to_wgs84, LonLatBbox, Tile};
+use tiledata::TileGrid;
+#[cfg(test)]
+use tiledata::TILE_SIZE;
+
+/// Defines the area of interest of a rendering,
+/// pixels outside of it are rendered as transparent.
+pub enum Mask {
+    /// Keeps the pixels where the value of another variable lies in [min; max],
+    /// eg: the sea pixels of a land-sea mask (`lsm` from 0 to 0.5).
+    /// Pixels where the mask variable is missing are masked as well.
+    Variable {
+        source: Box<dyn DataSource>,
+        min: f32,
+        max: f32,
+    },
+    /// Keeps the pixels located inside at least one of the zones
+    /// (eg: national borders read with `Zone::from_geojson`).
+    Zones(Vec<Zone>),
+}
+
+/// Returns true if both bounding boxes overlap
+fn intersects(a: &LonLatBbox, b: &LonLatBbox) -> bool {
+    a.west <= b.east && b.west <= a.east && a.south <= b.north && b.south <= a.north
+}
+
+impl Mask {
+    /// Sets to NaN the pixels of a tile grid (as returned by `TileData::to_tile_grid`)
+    /// which are outside of the mask.
+    pub fn apply(&self, tile: &Tile, grid: &mut TileGrid) {
+        match *self {
+            Mask::Variable { ref source, min, max } => {
+                let mask = match source.sized_tile_data(tile, 0, grid.size) {
+                    Ok(tile_data) => tile_data.to_tile_grid_with(
+                        grid.size,
+                        &Resampling::Auto,
+                        &Aggregation::Mean,
+                        None,
+                    ),
+                    // the tile is outside of the mask variable
+                    Err(_) => TileGrid::new(grid.size),
+                };
+                for (value, mask_value) in grid.values.iter_mut().zip(mask.values.iter()) {
+                    if !(*mask_value >= min && *mask_value <= max) {
+                        *value = f32::NAN;
+                    }
+                }
+            }
+            Mask::Zones(ref zones) => {
+                let bounds = tile.bounds();
+                let zones: Vec<&Zone> = zones
+                    .iter()
+                    .filter(|zone| zone.bounds().is_some_and(|b| intersects(&b, &bounds)))
+                    .collect();
+                // pixel centers, from the south west corner
+                let bbox = tile.xy_bounds();
+                let inc = (bbox.east - bbox.west) / grid.size as f64;
+                let lons: Vec<f64> = (0..grid.size)
+                    .map(|i| lon_meters_to_wgs84(bbox.west + inc * (0.5 + i as f64)))
+                    .collect();
+                for (i_lat, row) in grid.rows_mut().enumerate() {
+                    let lat = lat_meters_to_wgs84(bbox.south + inc * (0.5 + i_lat as f64));
+                    for (value, lon) in row.iter_mut().zip(lons.iter()) {
+                        if !value.is_nan() && !zones.iter().any(|zone| zone.contains(*lon, lat)) {
+                            *value = f32::NAN;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_zones_mask() {
+    let zones = Zone::from_geojson(
+        r#"{"type": "Polygon", "coordinates": [[[0, 0], [90, 0], [90, 60], [0, 60], [0, 0]]]}"#,
+    ).unwrap();
+    let mask = Mask::Zones(zones);
+    let mut grid = TileGrid { size: TILE_SIZE, values: vec![1.; TILE_SIZE * TILE_SIZE] };
+    mask.apply(&Tile { x: 0, y: 0, z: 0 }, &mut grid);
+    // rows go from south to north
+    assert!(grid[TILE_SIZE / 2 + 10][TILE_SIZE / 2 + 10] == 1.);
+    assert!(grid[TILE_SIZE / 2 - 10][TILE_SIZE / 2 + 10].is_nan());
+    assert!(grid[TILE_SIZE / 2 + 10][TILE_SIZE / 2 - 10].is_nan());
+
+    // tiles away from the zones are entirely masked
+    let mut grid = TileGrid { size: 512, values: vec![1.; 512 * 512] };
+    mask.apply(&Tile { x: 0, y: 1, z: 1 }, &mut grid);
+    assert!(grid.values.iter().all(|v| v.is_nan()));
+}
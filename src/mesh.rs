@@ -0,0 +1,363 @@
+use std::f32;
+use netcdf;
+use netcdf::attribute::AttrValue;
+use netcdf::variable::Variable;
+use dataset::{format_error, variable_fill_value, variable_string_attribute};
+use source::DataSource;
+use tile::{wgs84_to_meters, Tile};
+use tiledata::{TileData, TILE_SIZE};
+use utils::cell_edges;
+
+/// Unstructured triangular mesh data (UGRID convention), as written by
+/// coastal and hydrodynamic models such as ADCIRC, SCHISM or FVCOM.
+///
+/// Triangles are rasterized into the tile grid,
+/// values being interpolated using barycentric coordinates.
+pub struct MeshDataset {
+    /// Web mercator coordinates of the nodes (meters)
+    x: Vec<f64>,
+    y: Vec<f64>,
+    /// Node indices of each triangle
+    triangles: Vec<[usize; 3]>,
+    /// Index of the face each triangle was split from
+    faces: Vec<usize>,
+    /// Values, either one per node or one per face
+    values: Vec<f32>,
+    on_faces: bool,
+    /// Spatial index: triangles grouped into a regular grid of bins
+    min_x: f64,
+    min_y: f64,
+    bin_size: f64,
+    bins_x: usize,
+    bins_y: usize,
+    bins: Vec<Vec<usize>>,
+}
+
+/// Reads a whole variable as f64, other dimensions than `keep` are read at their first index
+fn read_variable(variable: &Variable, keep: &[String]) -> Result<Vec<f64>, String> {
+    let start: Vec<usize> = vec![0; variable.dimensions().len()];
+    let count: Vec<usize> = variable
+        .dimensions()
+        .iter()
+        .map(|d| if keep.contains(&d.name()) { d.len() } else { 1 })
+        .collect();
+    let mut values: Vec<f64> = vec![0.; count.iter().product()];
+    variable
+        .values_to(values.as_mut_slice(), Some(&start), Some(&count))
+        .map_err(format_error)?;
+    Ok(values)
+}
+
+/// Returns an integer attribute of a variable, if any
+fn variable_int_attribute(variable: &Variable, name: &str) -> Option<i64> {
+    match variable.attribute(name)?.value() {
+        Ok(AttrValue::Int(x)) => Some(x as i64),
+        Ok(AttrValue::Short(x)) => Some(x as i64),
+        Ok(AttrValue::Longlong(x)) => Some(x),
+        Ok(AttrValue::Uint(x)) => Some(x as i64),
+        Ok(AttrValue::Double(x)) => Some(x as i64),
+        Ok(AttrValue::Float(x)) => Some(x as i64),
+        _ => None,
+    }
+}
+
+impl MeshDataset {
+    /// Creates a MeshDataset from a UGRID netCDF file.
+    ///
+    /// #Args
+    ///  * `mesh` name of the mesh topology variable, its `node_coordinates` and
+    ///    `face_node_connectivity` attributes locate the nodes and faces
+    ///    (node coordinates must be expressed in WGS 84).
+    ///  * `variable` name of the variable to render, defined on the nodes or the faces
+    ///    (`location` attribute), any other dimension (eg: time) is read at its first index.
+    ///  * `file_path` path to the netCDF file.
+    pub fn new(mesh: &str, variable: &str, file_path: &str) -> Result<Self, String> {
+        let file = netcdf::open(file_path).map_err(format_error)?;
+        let root = file.root().ok_or("No root group")?;
+        let topology = root.variable(mesh).ok_or("No mesh topology variable")?;
+
+        // node coordinates
+        let node_coordinates = variable_string_attribute(&topology, "node_coordinates")
+            .ok_or("No node_coordinates attribute")?;
+        let names: Vec<&str> = node_coordinates.split_whitespace().collect();
+        if names.len() < 2 {
+            return Err("Invalid node_coordinates attribute".into());
+        }
+        let lon_var = root.variable(names[0]).ok_or("No node longitude variable")?;
+        let lat_var = root.variable(names[1]).ok_or("No node latitude variable")?;
+        let node_dim = lon_var.dimensions().first().ok_or("No node dimension")?.name();
+        let lons = read_variable(&lon_var, std::slice::from_ref(&node_dim))?;
+        let lats = read_variable(&lat_var, std::slice::from_ref(&node_dim))?;
+
+        // face connectivity
+        let connectivity_name = variable_string_attribute(&topology, "face_node_connectivity")
+            .ok_or("No face_node_connectivity attribute")?;
+        let connectivity_var = root
+            .variable(&connectivity_name)
+            .ok_or("No face node connectivity variable")?;
+        let dims: Vec<String> = connectivity_var.dimensions().iter().map(|d| d.name()).collect();
+        if dims.len() != 2 {
+            return Err("The face node connectivity must be bi-dimensional".into());
+        }
+        let face_dim = dims[0].clone();
+        let max_nodes = connectivity_var.dimensions()[1].len();
+        let connectivity = read_variable(&connectivity_var, &dims)?;
+        let start_index = variable_int_attribute(&connectivity_var, "start_index").unwrap_or(0);
+        let fill_value = variable_int_attribute(&connectivity_var, "_FillValue");
+        let faces: Vec<Vec<usize>> = connectivity
+            .chunks(max_nodes)
+            .map(|face| {
+                face.iter()
+                    .map(|node| *node as i64)
+                    .filter(|node| Some(*node) != fill_value && *node >= start_index)
+                    .map(|node| (node - start_index) as usize)
+                    .collect()
+            })
+            .collect();
+
+        // values
+        let data_var = root.variable(variable).ok_or("No variable found")?;
+        let on_faces = variable_string_attribute(&data_var, "location")
+            .map(|location| location == "face")
+            .unwrap_or_else(|| data_var.dimensions().iter().any(|d| d.name() == face_dim));
+        let location_dim = if on_faces { face_dim } else { node_dim };
+        let mut values: Vec<f32> = read_variable(&data_var, &[location_dim])?
+            .into_iter()
+            .map(|v| v as f32)
+            .collect();
+        if let Some(fill_value) = variable_fill_value(&data_var) {
+            for v in values.iter_mut() {
+                if *v == fill_value {
+                    *v = f32::NAN;
+                }
+            }
+        }
+
+        let nodes: Vec<(f64, f64)> = lons.into_iter().zip(lats).collect();
+        Self::from_faces(&nodes, &faces, values, on_faces)
+    }
+
+    /// Creates a MeshDataset from triangles, with one value per node.
+    ///
+    /// #Args
+    ///  * `nodes` (lon, lat) coordinates of the nodes (WGS 84)
+    ///  * `triangles` node indices of each triangle
+    ///  * `values` value of each node
+    pub fn from_triangles(
+        nodes: &[(f64, f64)],
+        triangles: &[[usize; 3]],
+        values: Vec<f32>,
+    ) -> Result<Self, String> {
+        let faces: Vec<Vec<usize>> = triangles.iter().map(|t| t.to_vec()).collect();
+        Self::from_faces(nodes, &faces, values, false)
+    }
+
+    /// Creates a MeshDataset from polygonal faces (split into triangles)
+    fn from_faces(
+        nodes: &[(f64, f64)],
+        faces: &[Vec<usize>],
+        values: Vec<f32>,
+        on_faces: bool,
+    ) -> Result<Self, String> {
+        let expected = if on_faces { faces.len() } else { nodes.len() };
+        if values.len() != expected {
+            return Err("The number of values does not match the mesh".into());
+        }
+        let (x, y): (Vec<f64>, Vec<f64>) =
+            nodes.iter().map(|&(lon, lat)| wgs84_to_meters(lon, lat)).unzip();
+
+        // split each face into a triangle fan
+        let mut triangles: Vec<[usize; 3]> = Vec::with_capacity(faces.len());
+        let mut face_indices: Vec<usize> = Vec::with_capacity(faces.len());
+        for (face_idx, face) in faces.iter().enumerate() {
+            if face.iter().any(|node| *node >= nodes.len()) {
+                return Err(format!("Face {} references an unknown node", face_idx));
+            }
+            for i in 1..face.len().saturating_sub(1) {
+                triangles.push([face[0], face[i], face[i + 1]]);
+                face_indices.push(face_idx);
+            }
+        }
+        if triangles.is_empty() {
+            return Err("The mesh has no triangle".into());
+        }
+
+        // build the spatial index, with roughly one triangle per bin
+        let min_x = x.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max_x = x.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let min_y = y.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max_y = y.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let side = (triangles.len() as f64).sqrt().ceil();
+        let bin_size = ((max_x - min_x).max(max_y - min_y) / side).max(1.);
+        let bins_x = ((max_x - min_x) / bin_size).floor() as usize + 1;
+        let bins_y = ((max_y - min_y) / bin_size).floor() as usize + 1;
+        let mut mesh = Self {
+            x,
+            y,
+            triangles,
+            faces: face_indices,
+            values,
+            on_faces,
+            min_x,
+            min_y,
+            bin_size,
+            bins_x,
+            bins_y,
+            bins: vec![Vec::new(); bins_x * bins_y],
+        };
+        for t in 0..mesh.triangles.len() {
+            let (west, south, east, north) = mesh.triangle_bounds(t);
+            let (i_min, j_min) = mesh.bin(west, south);
+            let (i_max, j_max) = mesh.bin(east, north);
+            for j in j_min..(j_max + 1) {
+                for i in i_min..(i_max + 1) {
+                    mesh.bins[j * bins_x + i].push(t);
+                }
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Returns the bin containing (x, y), clamped to the mesh extent
+    fn bin(&self, x: f64, y: f64) -> (usize, usize) {
+        let i = ((x - self.min_x) / self.bin_size).floor().max(0.) as usize;
+        let j = ((y - self.min_y) / self.bin_size).floor().max(0.) as usize;
+        (i.min(self.bins_x - 1), j.min(self.bins_y - 1))
+    }
+
+    /// Returns the (west, south, east, north) bounds of a triangle
+    fn triangle_bounds(&self, t: usize) -> (f64, f64, f64, f64) {
+        let [a, b, c] = self.triangles[t];
+        (
+            self.x[a].min(self.x[b]).min(self.x[c]),
+            self.y[a].min(self.y[b]).min(self.y[c]),
+            self.x[a].max(self.x[b]).max(self.x[c]),
+            self.y[a].max(self.y[b]).max(self.y[c]),
+        )
+    }
+
+    /// Returns the triangles whose bounds may intersect [west; east] x [south; north]
+    fn candidates(&self, west: f64, south: f64, east: f64, north: f64) -> Vec<usize> {
+        let mut candidates: Vec<usize> = Vec::new();
+        if east < self.min_x || north < self.min_y {
+            return candidates;
+        }
+        let (i_min, j_min) = self.bin(west, south);
+        let (i_max, j_max) = self.bin(east, north);
+        for j in j_min..(j_max + 1) {
+            for i in i_min..(i_max + 1) {
+                candidates.extend(&self.bins[j * self.bins_x + i]);
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Interpolates the value of the triangle `t` at (px, py),
+    /// returns None if the point lies outside of the triangle.
+    fn interpolate(&self, t: usize, px: f64, py: f64) -> Option<f32> {
+        let [a, b, c] = self.triangles[t];
+        let (x0, y0, x1, y1, x2, y2) = (self.x[a], self.y[a], self.x[b], self.y[b], self.x[c], self.y[c]);
+        let denominator = (y1 - y2) * (x0 - x2) + (x2 - x1) * (y0 - y2);
+        if denominator == 0. {
+            return None;
+        }
+        // barycentric coordinates
+        let l0 = ((y1 - y2) * (px - x2) + (x2 - x1) * (py - y2)) / denominator;
+        let l1 = ((y2 - y0) * (px - x2) + (x0 - x2) * (py - y2)) / denominator;
+        let l2 = 1. - l0 - l1;
+        let epsilon = -1e-9;
+        if l0 < epsilon || l1 < epsilon || l2 < epsilon {
+            return None;
+        }
+        if self.on_faces {
+            return Some(self.values[self.faces[t]]);
+        }
+        Some(
+            (l0 * self.values[a] as f64 + l1 * self.values[b] as f64 + l2 * self.values[c] as f64)
+                as f32,
+        )
+    }
+}
+
+impl DataSource for MeshDataset {
+    fn tile_data(&self, tile: &Tile, buffer: usize) -> Result<TileData, String> {
+        let bbox = tile.xy_bounds();
+        // rasterize the triangles on the pixel centers of the tile (and its buffer)
+        let n = TILE_SIZE + 2 * buffer;
+        let cell_size = (bbox.east - bbox.west) / TILE_SIZE as f64;
+        let x0 = bbox.west + cell_size * (0.5 - buffer as f64);
+        let y0 = bbox.south + cell_size * (0.5 - buffer as f64);
+        let x_max = x0 + cell_size * (n - 1) as f64;
+        let y_max = y0 + cell_size * (n - 1) as f64;
+
+        let candidates = self.candidates(x0, y0, x_max, y_max);
+        if candidates.is_empty() {
+            return Err("tile outside range".into());
+        }
+        let mut values = vec![f32::NAN; n * n];
+        // returns the range of pixel indices whose centers lie in [min; max]
+        let pixel_range = |min: f64, max: f64, origin: f64| -> (usize, usize) {
+            let first = ((min - origin) / cell_size).ceil().max(0.) as usize;
+            let last = ((max - origin) / cell_size).floor().min((n - 1) as f64);
+            (first, if last < 0. { 0 } else { last as usize + 1 })
+        };
+        for t in candidates {
+            let (west, south, east, north) = self.triangle_bounds(t);
+            let (i_min, i_max) = pixel_range(west, east, x0);
+            let (j_min, j_max) = pixel_range(south, north, y0);
+            for j in j_min..j_max {
+                for i in i_min..i_max {
+                    let (px, py) = (x0 + i as f64 * cell_size, y0 + j as f64 * cell_size);
+                    if let Some(value) = self.interpolate(t, px, py) {
+                        values[j * n + i] = value;
+                    }
+                }
+            }
+        }
+
+        let lon: Vec<f64> = (0..n).map(|i| x0 + i as f64 * cell_size).collect();
+        let lat: Vec<f64> = (0..n).map(|j| y0 + j as f64 * cell_size).collect();
+        Ok(TileData {
+            lon_edges: cell_edges(&lon),
+            lat_edges: cell_edges(&lat),
+            min_lon: lon[0],
+            max_lon: lon[n - 1],
+            lon,
+            min_lat: lat[0],
+            max_lat: lat[n - 1],
+            lat,
+            values,
+            bbox,
+            tile: Tile { x: tile.x, y: tile.y, z: tile.z },
+        })
+    }
+
+    fn value_at_coordinates(&self, lat: f64, lon: f64) -> Result<f32, String> {
+        let (x, y) = wgs84_to_meters(lon, lat);
+        self.candidates(x, y, x, y)
+            .into_iter()
+            .filter_map(|t| self.interpolate(t, x, y))
+            .next()
+            .ok_or_else(|| "Point outside of the mesh".into())
+    }
+}
+
+#[test]
+fn test_mesh_interpolation() {
+    // a square split into 2 triangles
+    let nodes = [(0., 0.), (10., 0.), (10., 10.), (0., 10.)];
+    let mesh = MeshDataset::from_triangles(&nodes, &[[0, 1, 2], [0, 2, 3]], vec![0., 10., 20., 10.])
+        .unwrap();
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+    assert!(close(mesh.value_at_coordinates(0., 0.).unwrap(), 0.));
+    assert!(close(mesh.value_at_coordinates(0., 10.).unwrap(), 10.));
+    assert!(mesh.value_at_coordinates(20., 20.).is_err());
+
+    let tile_data = mesh.tile_data(&Tile { x: 0, y: 0, z: 0 }, 0).unwrap();
+    assert_eq!(tile_data.values.len(), TILE_SIZE * TILE_SIZE);
+    let covered = tile_data.values.iter().filter(|v| !v.is_nan()).count();
+    assert!(covered > 0 && covered < TILE_SIZE * TILE_SIZE / 100);
+    assert!(mesh.tile_data(&Tile { x: 0, y: 0, z: 1 }, 0).is_err());
+}
@@ -0,0 +1,303 @@
+use std::f32;
+use std::fs;
+use std::path::Path;
+use aggregation::Aggregation;
+#[cfg(test)]
+use tiledata::TILE_SIZE;
+use tile::lat_meters_to_wgs84;
+
+// first bytes of an overview file, followed by the format version
+const MAGIC: &[u8; 8] = b"TILEROVR";
+const VERSION: u32 = 2;
+
+/// Code of an aggregation in an overview file
+fn aggregation_code(aggregation: &Aggregation) -> u32 {
+    match *aggregation {
+        Aggregation::Mean => 0,
+        Aggregation::Min => 1,
+        Aggregation::Max => 2,
+        Aggregation::Median => 3,
+        Aggregation::Mode => 4,
+        Aggregation::FirstValid => 5,
+        Aggregation::Conservative => 6,
+    }
+}
+
+/// A decimated copy of the (lat, lon) grid of a dataset variable,
+/// each cell aggregating 2^level x 2^level cells of the original grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overview {
+    /// meters (Web Mercator), ascending
+    pub lat: Vec<f64>,
+    /// cell edges, meters (Web Mercator)
+    pub lat_edges: Vec<f64>,
+    /// meters (Web Mercator), ascending
+    pub lon: Vec<f64>,
+    /// cell edges, meters (Web Mercator)
+    pub lon_edges: Vec<f64>,
+    /// flattened array (lat, lon), NaN where there is no value
+    pub values: Vec<f32>,
+}
+
+/// Merges the coordinates of consecutive pairs of cells,
+/// returns the (centers, edges) of the merged cells
+fn decimate_axis(centers: &[f64], edges: &[f64]) -> (Vec<f64>, Vec<f64>) {
+    let merged: Vec<f64> = centers
+        .chunks(2)
+        .map(|pair| pair.iter().sum::<f64>() / pair.len() as f64)
+        .collect();
+    let mut merged_edges: Vec<f64> = edges.iter().step_by(2).cloned().collect();
+    if centers.len() % 2 == 1 {
+        merged_edges.push(edges[centers.len()]);
+    }
+    (merged, merged_edges)
+}
+
+impl Overview {
+    /// Area (on the sphere, up to a constant factor) of the cell at (`i_lat`, `i_lon`)
+    fn cell_area(&self, i_lat: usize, i_lon: usize) -> f64 {
+        let sin = |y: f64| lat_meters_to_wgs84(y).to_radians().sin();
+        let height = (sin(self.lat_edges[i_lat + 1]) - sin(self.lat_edges[i_lat])).abs();
+        height * (self.lon_edges[i_lon + 1] - self.lon_edges[i_lon]).abs()
+    }
+
+    /// Aggregates the valid values of each 2 x 2 block of cells of the overview,
+    /// `Aggregation::Conservative` weighting them by their area
+    pub fn decimate(&self, aggregation: &Aggregation) -> Overview {
+        let (lat, lat_edges) = decimate_axis(&self.lat, &self.lat_edges);
+        let (lon, lon_edges) = decimate_axis(&self.lon, &self.lon_edges);
+        let n_lon = self.lon.len();
+        let mut values: Vec<f32> = Vec::with_capacity(lat.len() * lon.len());
+        let mut block: Vec<f32> = Vec::with_capacity(4);
+        for i in 0..lat.len() {
+            for j in 0..lon.len() {
+                block.clear();
+                let (mut weighted_sum, mut weights) = (0_f64, 0_f64);
+                for ii in (2 * i)..(2 * i + 2).min(self.lat.len()) {
+                    for jj in (2 * j)..(2 * j + 2).min(n_lon) {
+                        let value = self.values[ii * n_lon + jj];
+                        if *aggregation == Aggregation::Conservative && !value.is_nan() {
+                            let area = self.cell_area(ii, jj);
+                            weighted_sum += value as f64 * area;
+                            weights += area;
+                        }
+                        block.push(value);
+                    }
+                }
+                values.push(match *aggregation {
+                    Aggregation::Conservative if weights > 0. => (weighted_sum / weights) as f32,
+                    _ => aggregation.aggregate(&mut block),
+                });
+            }
+        }
+        Overview { lat, lat_edges, lon, lon_edges, values }
+    }
+
+    /// Average width and height (meters) of the cells between the indices
+    /// `i_lat_min..=i_lat_max` and `i_lon_min..=i_lon_max`
+    pub fn spacing(&self, i_lat_min: usize, i_lat_max: usize, i_lon_min: usize, i_lon_max: usize) -> (f64, f64) {
+        let width = (self.lon_edges[i_lon_max + 1] - self.lon_edges[i_lon_min]).abs();
+        let height = (self.lat_edges[i_lat_max + 1] - self.lat_edges[i_lat_min]).abs();
+        (
+            width / (i_lon_max - i_lon_min + 1) as f64,
+            height / (i_lat_max - i_lat_min + 1) as f64,
+        )
+    }
+}
+
+/// Builds the overview levels of a grid: each level halves the resolution
+/// of the previous one (aggregating its cells with `aggregation`),
+/// down to a single cell, so tiles of any size (or scale factor) find the overview
+/// resolving their pixels. The grid itself is not included.
+pub fn build_overviews(grid: Overview, aggregation: &Aggregation) -> Vec<Overview> {
+    let mut overviews: Vec<Overview> = Vec::new();
+    let mut previous = grid;
+    while previous.lat.len().max(previous.lon.len()) > 1 {
+        let overview = previous.decimate(aggregation);
+        overviews.push(overview.clone());
+        previous = overview;
+    }
+    overviews
+}
+
+/// Reads little endian values from an overview file
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < n {
+            return Err("Truncated overview file".into());
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut buffer = [0_u8; 4];
+        buffer.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut buffer = [0_u8; 8];
+        buffer.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    fn f64s(&mut self, n: usize) -> Result<Vec<f64>, String> {
+        Ok(self
+            .take(8 * n)?
+            .chunks(8)
+            .map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+            .collect())
+    }
+
+    fn f32s(&mut self, n: usize) -> Result<Vec<f32>, String> {
+        Ok(self
+            .take(4 * n)?
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+}
+
+/**
+ * Writes the overviews of `variable` (whose grid is `shape` = (n_lat, n_lon) cells),
+ * built with `aggregation`, into a sidecar file.
+ *
+ * #Format
+ *
+ * All numbers are little endian:
+ * `TILEROVR`, version (u32), aggregation code (u32), variable name length (u64) and bytes,
+ * n_lat (u64), n_lon (u64),
+ * number of levels (u64), then for each level: its n_lat (u64), n_lon (u64),
+ * lat, lat_edges, lon, lon_edges (f64) and values (f32).
+ */
+pub fn save_overviews<P: AsRef<Path>>(
+    path: P,
+    variable: &str,
+    shape: (usize, usize),
+    aggregation: &Aggregation,
+    overviews: &[Overview],
+) -> Result<(), String> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&aggregation_code(aggregation).to_le_bytes());
+    bytes.extend_from_slice(&(variable.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(variable.as_bytes());
+    for n in [shape.0, shape.1, overviews.len()] {
+        bytes.extend_from_slice(&(n as u64).to_le_bytes());
+    }
+    for overview in overviews {
+        bytes.extend_from_slice(&(overview.lat.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(overview.lon.len() as u64).to_le_bytes());
+        for coordinates in [&overview.lat, &overview.lat_edges, &overview.lon, &overview.lon_edges] {
+            for x in coordinates.iter() {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+        for value in &overview.values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    fs::write(path, bytes).map_err(|e| format!("Can not write the overview file: {}", e))
+}
+
+/// Reads the overviews of `variable` (whose grid is `shape` = (n_lat, n_lon) cells),
+/// built with `aggregation`, from a sidecar file written by `save_overviews`.
+pub fn load_overviews<P: AsRef<Path>>(
+    path: P,
+    variable: &str,
+    shape: (usize, usize),
+    aggregation: &Aggregation,
+) -> Result<Vec<Overview>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Can not read the overview file: {}", e))?;
+    let mut reader = Reader { bytes: &bytes };
+    if reader.take(8)? != MAGIC || reader.take(4)? != VERSION.to_le_bytes() {
+        return Err("Not an overview file, or an unsupported version".into());
+    }
+    if reader.u32()? != aggregation_code(aggregation) {
+        return Err(format!("The overview file was not built with the {:?} aggregation", aggregation));
+    }
+    let name_length = reader.u64()? as usize;
+    if reader.take(name_length)? != variable.as_bytes() {
+        return Err(format!("The overview file does not describe {}", variable));
+    }
+    if (reader.u64()? as usize, reader.u64()? as usize) != shape {
+        return Err("The overview file does not match the dataset grid".into());
+    }
+    let levels = reader.u64()?;
+    let mut overviews: Vec<Overview> = Vec::new();
+    for _ in 0..levels {
+        let n_lat = reader.u64()? as usize;
+        let n_lon = reader.u64()? as usize;
+        overviews.push(Overview {
+            lat: reader.f64s(n_lat)?,
+            lat_edges: reader.f64s(n_lat + 1)?,
+            lon: reader.f64s(n_lon)?,
+            lon_edges: reader.f64s(n_lon + 1)?,
+            values: reader.f32s(n_lat * n_lon)?,
+        });
+    }
+    Ok(overviews)
+}
+
+#[test]
+fn test_overviews() {
+    use utils::cell_edges;
+    let n = 2 * TILE_SIZE + 1;
+    let coordinates: Vec<f64> = (0..n).map(|i| i as f64).collect();
+    let mut values: Vec<f32> = (0..(n * n)).map(|i| (i % n) as f32).collect();
+    values[0] = f32::NAN;
+    let grid = Overview {
+        lat: coordinates.clone(),
+        lat_edges: cell_edges(&coordinates),
+        lon: coordinates.clone(),
+        lon_edges: cell_edges(&coordinates),
+        values,
+    };
+    let overviews = build_overviews(grid.clone(), &Aggregation::Mean);
+    // 513 cells -> 257 -> 129 -> 65 -> 33 -> 17 -> 9 -> 5 -> 3 -> 2 -> 1
+    assert_eq!(overviews.len(), 10);
+    assert_eq!(overviews[9].values.len(), 1);
+    assert_eq!(overviews[0].lon.len(), TILE_SIZE + 1);
+    assert_eq!(overviews[0].lon_edges.len(), TILE_SIZE + 2);
+    assert_eq!(overviews[0].lon_edges[TILE_SIZE + 1], n as f64 - 0.5);
+    // NaN are ignored: (1 + 0 + 1) / 3
+    assert!((overviews[0].values[0] - 2. / 3.).abs() < 1e-6);
+    assert_eq!(overviews[0].values[1], 2.5);
+    assert_eq!(overviews[1].spacing(0, 1, 0, 1), (4., 4.));
+
+    // the peaks are kept by the Max aggregation
+    let maxima = build_overviews(grid, &Aggregation::Max);
+    assert_eq!(maxima[0].values[0], 1.);
+    assert_eq!(maxima[1].values[1], 7.);
+
+    // the cells closer to the pole cover less area
+    let cells = Overview {
+        lat: vec![2.5e6, 7.5e6],
+        lat_edges: vec![0., 5e6, 1e7],
+        lon: vec![0.],
+        lon_edges: vec![-1., 1.],
+        values: vec![1., 0.],
+    };
+    assert_eq!(cells.decimate(&Aggregation::Mean).values, vec![0.5]);
+    assert!(cells.decimate(&Aggregation::Conservative).values[0] > 0.5);
+
+    // a unique name, so concurrent test runs don't share the file
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    let path = std::env::temp_dir().join(format!("tiler_test_overviews_{}_{}.ovr", std::process::id(), nanos));
+    save_overviews(&path, "v", (n, n), &Aggregation::Mean, &overviews).unwrap();
+    let loaded = load_overviews(&path, "v", (n, n), &Aggregation::Mean).unwrap();
+    assert_eq!(loaded, overviews);
+    assert!(load_overviews(&path, "w", (n, n), &Aggregation::Mean).is_err());
+    assert!(load_overviews(&path, "v", (n, n + 1), &Aggregation::Mean).is_err());
+    assert!(load_overviews(&path, "v", (n, n), &Aggregation::Max).is_err());
+    let _ = fs::remove_file(&path);
+}
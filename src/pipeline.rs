@@ -0,0 +1,320 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use source::DataSource;
+use tiledata::{TileData, TileGrid, TILE_SIZE};
+use tile::Tile;
+use gapfill::GapFill;
+use filter::Filter;
+use mask::Mask;
+use resampling::Resampling;
+use aggregation::Aggregation;
+use renderer::ImgTile;
+
+/// Largest number of tiles per side of a metatile (see `Renderer::render_metatile`):
+/// a 16 x 16 block of 1024 pixels tiles already holds 268M pixels.
+pub const MAX_METATILE_SIZE: u32 = 16;
+
+/// Largest size (pixels) of the rendered tiles, see `Renderer::with_tile_size`
+pub const MAX_TILE_SIZE: usize = 1024;
+
+/// Largest scale factor of the rendered tiles (eg: 2 for @2x retina tiles)
+pub const MAX_SCALE_FACTOR: usize = 4;
+
+/// Returns `tile_size` if tiles of that size can be rendered:
+/// a power of 2 up to MAX_TILE_SIZE (eg: 256, 512 or 1024)
+pub(crate) fn check_tile_size(tile_size: usize) -> Result<usize, String> {
+    if !tile_size.is_power_of_two() || tile_size > MAX_TILE_SIZE {
+        return Err(format!("The tile size must be a power of 2, up to {}", MAX_TILE_SIZE));
+    }
+    Ok(tile_size)
+}
+
+/// Returns the size (pixels) of the tiles rendered `scale_factor` times larger than `tile_size`
+pub(crate) fn scaled_tile_size(tile_size: usize, scale_factor: usize) -> Result<usize, String> {
+    if scale_factor == 0 || scale_factor > MAX_SCALE_FACTOR {
+        return Err(format!("The scale factor must be between 1 and {}", MAX_SCALE_FACTOR));
+    }
+    Ok(tile_size * scale_factor)
+}
+
+/// Converts the grids of a tile (one per source, resampled, filtered and masked)
+/// into RGBA pixels, rows going from north to south.
+pub(crate) type Colors<'a> = dyn Fn(&Tile, &[TileGrid]) -> Vec<u8> + Sync + 'a;
+
+/// The rendering steps shared by `Renderer`, `CompositeRenderer` and `BivariateRenderer`:
+/// extract the values of their sources, fill their gaps, resample, filter and mask them
+/// into tile grids, then walk tile pyramids (possibly with several threads) or metatiles.
+///
+/// Each renderer provides its sources and the conversion of their grids into pixels.
+pub(crate) struct Pipeline {
+    pub gap_fill: Option<GapFill>,
+    pub filter: Option<Filter>,
+    pub mask: Option<Mask>,
+    pub resampling: Resampling,
+    pub aggregation: Aggregation,
+    pub tile_size: usize,
+    pub threads: usize,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            gap_fill: None,
+            filter: None,
+            mask: None,
+            resampling: Resampling::Auto,
+            aggregation: Aggregation::Mean,
+            tile_size: TILE_SIZE,
+            threads: 1,
+        }
+    }
+
+    /// The filter to apply on tiles rendered with a scale factor
+    fn scaled_filter(&self, scale_factor: usize) -> Option<Filter> {
+        self.filter.map(|filter| filter.scaled(scale_factor))
+    }
+
+    /// Number of pixels (of a TILE_SIZE tile) needed around a tile by the filter,
+    /// when it is resampled into a `grid_size` wide grid
+    fn buffer(&self, scale_factor: usize, grid_size: usize) -> usize {
+        let radius = self.scaled_filter(scale_factor).map_or(0, |filter| filter.radius());
+        (radius * TILE_SIZE).div_ceil(grid_size)
+    }
+
+    /// Extract the values of a tile (and `buffer` pixels around it) from a data source,
+    /// at the resolution of `size` x `size` pixels, and fill their gaps.
+    ///
+    /// Gaps are measured on a window extended by `GapFill::margin` cells, so a gap is
+    /// filled (or not) the same way in every tile it overlaps, whatever the zoom level.
+    pub fn tile_data(&self, source: &dyn DataSource, tile: &Tile, buffer: usize, size: usize)
+            -> Result<TileData, String> {
+        let mut tile_data = source.sized_tile_data(tile, buffer, size)?;
+        if let Some(ref gap_fill) = self.gap_fill {
+            // the margin, in pixels of a TILE_SIZE tile
+            let pixel_size = (tile_data.bbox.east - tile_data.bbox.west).abs() / TILE_SIZE as f64;
+            let margin = (gap_fill.margin() as f64 * tile_data.max_cell_size() / pixel_size).ceil() as usize;
+            if margin > buffer {
+                tile_data = source.sized_tile_data(tile, margin, size)?;
+            }
+            tile_data.fill_gaps(gap_fill);
+        }
+        Ok(tile_data)
+    }
+
+    /// Extract the values of a tile from every source, see `Pipeline::tile_data`
+    fn sources_tile_data(&self, sources: &[&dyn DataSource], tile: &Tile, buffer: usize, size: usize)
+            -> Result<Vec<TileData>, String> {
+        sources.iter().map(|source| self.tile_data(*source, tile, buffer, size)).collect()
+    }
+
+    /// Resample (and filter) a TileData into a square grid of `grid_size` pixels
+    fn resample(&self, data: &TileData, grid_size: usize, scale_factor: usize) -> TileGrid {
+        data.to_tile_grid_with(
+            grid_size,
+            &self.resampling,
+            &self.aggregation,
+            self.scaled_filter(scale_factor).as_ref(),
+        )
+    }
+
+    /// Sets the pixels of a tile grid outside of the mask (if any) to NaN
+    pub fn apply_mask(&self, tile: &Tile, grid: &mut TileGrid) {
+        if let Some(ref mask) = self.mask {
+            mask.apply(tile, grid);
+        }
+    }
+
+    /// Resample (filter and mask) a TileData into a square grid of
+    /// `tile_size * scale_factor` pixels
+    pub fn tile_grid(&self, data: &TileData, scale_factor: usize) -> TileGrid {
+        let mut grid = self.resample(data, self.tile_size * scale_factor, scale_factor);
+        self.apply_mask(&data.tile, &mut grid);
+        grid
+    }
+
+    /// Render the TileData of each source into an ImgTile `scale_factor` times larger
+    fn render_tiledata(&self, data: &[TileData], scale_factor: usize, colors: &Colors) -> ImgTile {
+        let grids: Vec<TileGrid> = data.iter().map(|data| self.tile_grid(data, scale_factor)).collect();
+        let tile = &data[0].tile;
+        ImgTile {
+            pixels: colors(tile, &grids),
+            size: grids[0].size as u32,
+            x: tile.x,
+            y: tile.y,
+            z: tile.z,
+        }
+    }
+
+    /// Render a Tile into an ImgTile `scale_factor` times larger (eg: 2 for @2x retina tiles),
+    /// the filter is scaled accordingly, so the tiles look alike.
+    pub fn render_tile(&self, sources: &[&dyn DataSource], tile: &Tile, scale_factor: usize, colors: &Colors)
+            -> Result<ImgTile, String> {
+        let size = scaled_tile_size(self.tile_size, scale_factor)?;
+        let data = self.sources_tile_data(sources, tile, self.buffer(scale_factor, size), size)?;
+        Ok(self.render_tiledata(&data, scale_factor, colors))
+    }
+
+    /// Split the TileData of each source into the TileData of each sub-tile (one per source)
+    fn sub_tiledata(data: &[TileData], buffer: usize) -> Vec<Vec<TileData>> {
+        let mut sub_data: Vec<Vec<TileData>> = Vec::new();
+        for source_data in data {
+            for (k, sub) in source_data.buffered_sub_tiledata(buffer).into_iter().enumerate() {
+                if k == sub_data.len() {
+                    sub_data.push(Vec::with_capacity(data.len()));
+                }
+                sub_data[k].push(sub);
+            }
+        }
+        sub_data
+    }
+
+    /// This function render the TileData of each source and their `level` sub-levels into ImgTile,
+    /// by *RECURSIVELY* calling itself using `TileData::buffered_sub_tiledata`.
+    /// Each ImgTile is handed to `callback` as soon as it is rendered.
+    fn render_n_tiledata_zoom<F>(&self, data: &[TileData], level: u8, scale_factor: usize, colors: &Colors, callback: &mut F)
+        where F: FnMut(ImgTile) {
+        callback(self.render_tiledata(data, scale_factor, colors));
+
+        if level > 0 {
+            let buffer = self.buffer(scale_factor, self.tile_size * scale_factor);
+            for sub_data in Self::sub_tiledata(data, buffer) {
+                self.render_n_tiledata_zoom(&sub_data, level -1, scale_factor, colors, callback);
+            }
+        }
+    }
+
+    /// Render the TileData of each source and their `level` sub-levels into ImgTiles,
+    /// using `self.threads` threads.
+    ///
+    /// The first levels are rendered sequentially, until there are enough sub-trees
+    /// of tiles to keep every thread busy, then the threads render those sub-trees
+    /// (each one with `render_n_tiledata_zoom`) until none is left.
+    /// The rendered ImgTiles are sent back to the calling thread, which hands them to `callback`.
+    fn render_n_tiledata_zoom_parallel<F>(
+        &self,
+        data: Vec<TileData>,
+        mut level: u8,
+        scale_factor: usize,
+        colors: &Colors,
+        callback: &mut F,
+    ) where F: FnMut(ImgTile) {
+        let buffer = self.buffer(scale_factor, self.tile_size * scale_factor);
+        let mut sub_trees = vec![data];
+        while level > 0 && sub_trees.len() < 4 * self.threads {
+            let mut sub_data: Vec<Vec<TileData>> = Vec::new();
+            for data in sub_trees {
+                callback(self.render_tiledata(&data, scale_factor, colors));
+                sub_data.extend(Self::sub_tiledata(&data, buffer));
+            }
+            sub_trees = sub_data;
+            level -= 1;
+        }
+
+        let next = AtomicUsize::new(0);
+        let (sub_trees, next) = (&sub_trees, &next);
+        thread::scope(|scope| {
+            // a bounded channel, so the workers wait for `callback` instead of piling up tiles
+            let (sender, receiver) = mpsc::sync_channel(self.threads);
+            for _ in 0..self.threads.min(sub_trees.len()) {
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    while let Some(data) = sub_trees.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        self.render_n_tiledata_zoom(data, level, scale_factor, colors, &mut |img| {
+                            sender.send(img).expect("the rendered tiles receiver hung up")
+                        });
+                    }
+                });
+            }
+            drop(sender);
+            for img in receiver {
+                callback(img);
+            }
+        });
+    }
+
+    /// Render a tile and its `level` sub-levels into ImgTiles `scale_factor` times larger,
+    /// handing each one to `callback` (from the calling thread) as soon as it is rendered,
+    /// see `Renderer::render_n_level_tile_with`.
+    pub fn render_n_level_tile_with<F>(
+        &self,
+        sources: &[&dyn DataSource],
+        tile: &Tile,
+        level: u8,
+        scale_factor: usize,
+        colors: &Colors,
+        mut callback: F,
+    ) -> Result<(), String> where F: FnMut(ImgTile) {
+        let size = scaled_tile_size(self.tile_size, scale_factor)?;
+        // the values are those of the deepest level, whose tiles cover `size << level` pixels
+        let resolution = size.saturating_mul(1 << level.min(32));
+        let data = self.sources_tile_data(sources, tile, self.buffer(scale_factor, size), resolution)?;
+        if self.threads > 1 {
+            self.render_n_tiledata_zoom_parallel(data, level, scale_factor, colors, &mut callback);
+        } else {
+            self.render_n_tiledata_zoom(&data, level, scale_factor, colors, &mut callback);
+        }
+        Ok(())
+    }
+
+    /// Render the block of `metatile_size` x `metatile_size` tiles containing `tile`
+    /// into ImgTiles `scale_factor` times larger, see `Renderer::render_metatile`.
+    pub fn render_metatile(
+        &self,
+        sources: &[&dyn DataSource],
+        tile: &Tile,
+        metatile_size: u32,
+        scale_factor: usize,
+        colors: &Colors,
+    ) -> Result<Vec<ImgTile>, String> {
+        // the block of tiles is the tile `levels` zoom levels above
+        let metatile_size = metatile_size.clamp(1, MAX_METATILE_SIZE);
+        let levels = (31 - metatile_size.leading_zeros()).min(tile.z);
+        let n = 1_u32 << levels;
+        let block = Tile { x: tile.x >> levels, y: tile.y >> levels, z: tile.z - levels };
+        let tile_size = scaled_tile_size(self.tile_size, scale_factor)?;
+        let size = tile_size * n as usize;
+        let data = self.sources_tile_data(sources, &block, self.buffer(scale_factor, size), size)?;
+        let grids: Vec<TileGrid> = data.iter().map(|data| self.resample(data, size, scale_factor)).collect();
+
+        let mut imgs: Vec<ImgTile> = Vec::with_capacity((n * n) as usize);
+        for j in 0..n {
+            for i in 0..n {
+                let tile = Tile { x: (block.x << levels) + i, y: (block.y << levels) + j, z: tile.z };
+                // grid rows go from south to north, tiles from north to south
+                let i_lat = (n - 1 - j) as usize * tile_size;
+                let tile_grids: Vec<TileGrid> = grids
+                    .iter()
+                    .map(|grid| {
+                        let mut tile_grid = grid.crop(i_lat, i as usize * tile_size, tile_size);
+                        self.apply_mask(&tile, &mut tile_grid);
+                        tile_grid
+                    })
+                    .collect();
+                imgs.push(
+                    ImgTile {
+                        pixels: colors(&tile, &tile_grids),
+                        size: tile_size as u32,
+                        x: tile.x,
+                        y: tile.y,
+                        z: tile.z,
+                    }
+                );
+            }
+        }
+        Ok(imgs)
+    }
+}
+
+#[test]
+fn test_tile_sizes() {
+    for tile_size in [1, 256, 512, 1024] {
+        assert_eq!(check_tile_size(tile_size), Ok(tile_size));
+    }
+    for tile_size in [0, 3, 7, 100, 2048, 100_000] {
+        assert!(check_tile_size(tile_size).is_err());
+    }
+    assert_eq!(scaled_tile_size(256, 2), Ok(512));
+    assert!(scaled_tile_size(256, 0).is_err());
+    assert!(scaled_tile_size(256, MAX_SCALE_FACTOR + 1).is_err());
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::fs::File;
+use std::f32;
+use source::DataSource;
+use tile::{lat_meters_to_wgs84, wgs84_to_meters, Tile};
+use tiledata::{TileData, TILE_SIZE};
+use utils::cell_edges;
+
+/// Number of grid cells (per side) each tile is gridded into,
+/// the renderer then interpolates them into TILE_SIZE pixels.
+const POINT_GRID_SIZE: usize = TILE_SIZE / 2;
+
+/// Largest margin (cells) the natural neighbour gridding is extended by, see `PointData::grid`.
+/// Tiles only agree along their edges when the search radius fits in it
+/// (ie: up to a search radius of half a tile).
+const MAX_NATURAL_NEIGHBOUR_MARGIN: usize = POINT_GRID_SIZE / 2;
+
+/// Describes how scattered points are interpolated into a grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointInterpolation {
+    /// Inverse distance weighting, weights being `1 / distance ^ power`
+    InverseDistance { power: f32 },
+    /// Natural neighbour (Sibson) interpolation,
+    /// approximated on the tile grid (discrete Sibson interpolation).
+    NaturalNeighbour,
+}
+
+/// Unstructured point data (eg: station observations),
+/// gridded on the fly for each requested tile.
+pub struct PointData {
+    /// Web mercator coordinates of the points (meters)
+    x: Vec<f64>,
+    y: Vec<f64>,
+    values: Vec<f32>,
+    interpolation: PointInterpolation,
+    /// Max search radius around a grid cell (meters)
+    max_distance: f64,
+    /// Spatial index: points indices grouped into square bins of `max_distance` meters
+    bins: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl PointData {
+    /// Creates a PointData from (lon, lat, value) triplets (WGS84).
+    ///
+    /// #Args
+    ///  * `points` the observations, NaN values are ignored
+    ///  * `interpolation` the gridding method
+    ///  * `max_distance` grid cells further than `max_distance` meters from any point are left empty
+    pub fn new(
+        points: &[(f64, f64, f32)],
+        interpolation: PointInterpolation,
+        max_distance: f64,
+    ) -> Result<Self, String> {
+        if max_distance.is_nan() || max_distance <= 0. {
+            return Err("The search radius must be strictly positive".into());
+        }
+        let mut data = Self {
+            x: Vec::with_capacity(points.len()),
+            y: Vec::with_capacity(points.len()),
+            values: Vec::with_capacity(points.len()),
+            interpolation,
+            max_distance,
+            bins: HashMap::new(),
+        };
+        for &(lon, lat, value) in points.iter().filter(|p| !p.2.is_nan()) {
+            let (x, y) = wgs84_to_meters(lon, lat);
+            data.bins.entry(data.bin(x, y)).or_default().push(data.values.len());
+            data.x.push(x);
+            data.y.push(y);
+            data.values.push(value);
+        }
+        Ok(data)
+    }
+
+    /// Creates a PointData from a CSV file, with a header line.
+    ///
+    /// The longitude and latitude columns must be named `lon` (or `longitude`)
+    /// and `lat` (or `latitude`), values are read from the `value_column` column.
+    pub fn from_csv(
+        file_path: &str,
+        value_column: &str,
+        interpolation: PointInterpolation,
+        max_distance: f64,
+    ) -> Result<Self, String> {
+        let file = File::open(file_path).map_err(|e| e.to_string())?;
+        let mut lines = BufReader::new(file).lines();
+        let header: Vec<String> = lines
+            .next()
+            .ok_or("Empty CSV file")?
+            .map_err(|e| e.to_string())?
+            .split(',')
+            .map(|name| name.trim().trim_matches('"').to_lowercase())
+            .collect();
+        let column = |names: &[&str]| -> Result<usize, String> {
+            header
+                .iter()
+                .position(|name| names.contains(&name.as_str()))
+                .ok_or_else(|| format!("No {} column", names[0]))
+        };
+        let lon_idx = column(&["lon", "longitude"])?;
+        let lat_idx = column(&["lat", "latitude"])?;
+        let value_idx = column(&[value_column.to_lowercase().as_str()])?;
+
+        let mut points: Vec<(f64, f64, f32)> = Vec::new();
+        for line in lines {
+            let line = line.map_err(|e| e.to_string())?;
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            let field = |idx: usize| fields.get(idx).and_then(|f| f.parse::<f64>().ok());
+            // skip incomplete rows
+            if let (Some(lon), Some(lat), Some(value)) = (field(lon_idx), field(lat_idx), field(value_idx)) {
+                points.push((lon, lat, value as f32));
+            }
+        }
+        Self::new(&points, interpolation, max_distance)
+    }
+
+    /// Returns the bin a point falls into
+    fn bin(&self, x: f64, y: f64) -> (i64, i64) {
+        ((x / self.max_distance).floor() as i64, (y / self.max_distance).floor() as i64)
+    }
+
+    /// Web Mercator stretches distances by 1 / cos(latitude),
+    /// returns the search radius around `y` expressed in Web Mercator meters.
+    fn search_radius(&self, y: f64) -> f64 {
+        self.max_distance / lat_meters_to_wgs84(y).to_radians().cos().max(0.01)
+    }
+
+    /// Returns the (index, squared distance) of the points within `radius` of (x, y)
+    fn neighbours(&self, x: f64, y: f64, radius: f64) -> Vec<(usize, f64)> {
+        let mut neighbours: Vec<(usize, f64)> = Vec::new();
+        let mut push_if_close = |idx: usize| {
+            let d2 = (self.x[idx] - x).powi(2) + (self.y[idx] - y).powi(2);
+            if d2 <= radius * radius {
+                neighbours.push((idx, d2));
+            }
+        };
+        let (min_bin, max_bin) = (self.bin(x - radius, y - radius), self.bin(x + radius, y + radius));
+        let bin_count = (max_bin.0 - min_bin.0 + 1) as f64 * (max_bin.1 - min_bin.1 + 1) as f64;
+        if bin_count > self.bins.len() as f64 {
+            // the search area covers more bins than there are, check every point
+            for idx in 0..self.values.len() {
+                push_if_close(idx);
+            }
+        } else {
+            for bin_x in min_bin.0..(max_bin.0 + 1) {
+                for bin_y in min_bin.1..(max_bin.1 + 1) {
+                    if let Some(indices) = self.bins.get(&(bin_x, bin_y)) {
+                        for idx in indices {
+                            push_if_close(*idx);
+                        }
+                    }
+                }
+            }
+        }
+        neighbours
+    }
+
+    /// Inverse distance weighting of the points around (x, y)
+    fn inverse_distance(&self, x: f64, y: f64, power: f32) -> f32 {
+        let mut sum: f64 = 0.;
+        let mut weights: f64 = 0.;
+        for (idx, d2) in self.neighbours(x, y, self.search_radius(y)) {
+            if d2 == 0. {
+                return self.values[idx];
+            }
+            let weight = 1. / d2.powf(power as f64 / 2.);
+            sum += weight * self.values[idx] as f64;
+            weights += weight;
+        }
+        if weights == 0. {
+            return f32::NAN;
+        }
+        (sum / weights) as f32
+    }
+
+    /// Grid the points into `nx` x `ny` cells of `cell_size` meters,
+    /// the center of the first one being (x0, y0).
+    ///
+    /// Returns a flattened array (y, x).
+    fn grid(&self, x0: f64, y0: f64, cell_size: f64, nx: usize, ny: usize) -> Vec<f32> {
+        match self.interpolation {
+            PointInterpolation::InverseDistance { power } => {
+                let mut values: Vec<f32> = Vec::with_capacity(nx * ny);
+                for j in 0..ny {
+                    for i in 0..nx {
+                        let (x, y) = (x0 + i as f64 * cell_size, y0 + j as f64 * cell_size);
+                        values.push(self.inverse_distance(x, y, power));
+                    }
+                }
+                values
+            }
+            PointInterpolation::NaturalNeighbour => {
+                // Discrete Sibson interpolation (Park et al. 2006):
+                // each cell `c` spreads the value of its nearest point over every
+                // cell closer to `c` than that point, each cell then averages what it received.
+                // A cell only receives from cells within the search radius, so the grid is
+                // extended by that radius (up to MAX_NATURAL_NEIGHBOUR_MARGIN cells): the cells
+                // get the same value whatever the grid (tile, buffer) they are gridded in.
+                let max_radius = self
+                    .search_radius(y0)
+                    .max(self.search_radius(y0 + (ny as f64 - 1.) * cell_size));
+                let margin = ((max_radius / cell_size).ceil() as usize).min(MAX_NATURAL_NEIGHBOUR_MARGIN);
+                let (ex, ey) = (nx + 2 * margin, ny + 2 * margin);
+                let mut sums = vec![0_f64; ex * ey];
+                let mut counts = vec![0_u32; ex * ey];
+                let mut covered = vec![false; ex * ey];
+                for j in 0..ey {
+                    for i in 0..ex {
+                        let x = x0 + (i as f64 - margin as f64) * cell_size;
+                        let y = y0 + (j as f64 - margin as f64) * cell_size;
+                        let nearest = self
+                            .neighbours(x, y, self.search_radius(y))
+                            .into_iter()
+                            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                        let (idx, d2) = match nearest {
+                            Some(nearest) => nearest,
+                            None => continue,
+                        };
+                        covered[j * ex + i] = true;
+                        let radius = d2.sqrt() / cell_size;
+                        // only visit the cells of the extended grid
+                        let r = radius.floor() as usize;
+                        for cj in j.saturating_sub(r)..(j + r + 1).min(ey) {
+                            for ci in i.saturating_sub(r)..(i + r + 1).min(ex) {
+                                let (di, dj) = (ci as f64 - i as f64, cj as f64 - j as f64);
+                                if di * di + dj * dj <= radius * radius {
+                                    let c = cj * ex + ci;
+                                    sums[c] += self.values[idx] as f64;
+                                    counts[c] += 1;
+                                }
+                            }
+                        }
+                    }
+                }
+                let mut values: Vec<f32> = Vec::with_capacity(nx * ny);
+                for j in margin..(margin + ny) {
+                    for i in margin..(margin + nx) {
+                        let c = j * ex + i;
+                        values.push(if covered[c] && counts[c] > 0 {
+                            (sums[c] / counts[c] as f64) as f32
+                        } else {
+                            f32::NAN
+                        });
+                    }
+                }
+                values
+            }
+        }
+    }
+}
+
+impl DataSource for PointData {
+    fn tile_data(&self, tile: &Tile, buffer: usize) -> Result<TileData, String> {
+        let bbox = tile.xy_bounds();
+        // grid cells needed around the tile
+        let extra = (buffer * POINT_GRID_SIZE).div_ceil(TILE_SIZE);
+        let n = POINT_GRID_SIZE + 2 * extra;
+        let cell_size = (bbox.east - bbox.west) / POINT_GRID_SIZE as f64;
+        let x0 = bbox.west + cell_size * (0.5 - extra as f64);
+        let y0 = bbox.south + cell_size * (0.5 - extra as f64);
+
+        let values = self.grid(x0, y0, cell_size, n, n);
+        let lon: Vec<f64> = (0..n).map(|i| x0 + i as f64 * cell_size).collect();
+        let lat: Vec<f64> = (0..n).map(|j| y0 + j as f64 * cell_size).collect();
+        Ok(TileData {
+            lon_edges: cell_edges(&lon),
+            lat_edges: cell_edges(&lat),
+            min_lon: lon[0],
+            max_lon: lon[n - 1],
+            lon,
+            min_lat: lat[0],
+            max_lat: lat[n - 1],
+            lat,
+            values,
+            bbox,
+            tile: Tile { x: tile.x, y: tile.y, z: tile.z },
+        })
+    }
+
+    fn value_at_coordinates(&self, lat: f64, lon: f64) -> Result<f32, String> {
+        let (x, y) = wgs84_to_meters(lon, lat);
+        match self.interpolation {
+            PointInterpolation::InverseDistance { power } => Ok(self.inverse_distance(x, y, power)),
+            PointInterpolation::NaturalNeighbour => {
+                // the discrete Sibson interpolation needs a grid,
+                // grid a small neighbourhood around the point, and pick its center
+                let cell_size = self.search_radius(y) / 16.;
+                let values = self.grid(x - 16. * cell_size, y - 16. * cell_size, cell_size, 33, 33);
+                Ok(values[16 * 33 + 16])
+            }
+        }
+    }
+}
+
+#[test]
+fn test_inverse_distance_gridding() {
+    let points = [(0., 0., 1.), (1., 0., 3.), (50., 50., 100.)];
+    let interpolation = PointInterpolation::InverseDistance { power: 2. };
+    let data = PointData::new(&points, interpolation, 500_000.).unwrap();
+    // halfway between the 2 first points
+    assert!((data.value_at_coordinates(0., 0.5).unwrap() - 2.).abs() < 1e-3);
+    assert_eq!(data.value_at_coordinates(0., 1.).unwrap(), 3.);
+    // too far from any point
+    assert!(data.value_at_coordinates(-30., -30.).unwrap().is_nan());
+}
+
+#[test]
+fn test_natural_neighbour_gridding() {
+    let points = [(0., 0., 1.), (1., 0., 3.)];
+    let data = PointData::new(&points, PointInterpolation::NaturalNeighbour, 500_000.).unwrap();
+    // values are blended between the 2 points
+    let value = data.value_at_coordinates(0.5, 0.4).unwrap();
+    assert!(value > 1. && value < 3.);
+    assert_eq!(data.value_at_coordinates(0., 0.).unwrap(), 1.);
+    let tile_data = data.tile_data(&Tile { x: 0, y: 0, z: 0 }, 0).unwrap();
+    assert_eq!(tile_data.values.len(), POINT_GRID_SIZE * POINT_GRID_SIZE);
+    assert!(tile_data.values.iter().any(|v| !v.is_nan()));
+    assert!(tile_data.values.iter().any(|v| v.is_nan()));
+}
+
+#[test]
+fn test_natural_neighbour_tile_seams() {
+    let points: Vec<(f64, f64, f32)> = (0..60)
+        .map(|i| ((i * 37 % 180) as f64 - 90., (i * 23 % 120) as f64 - 60., i as f32))
+        .collect();
+    let data = PointData::new(&points, PointInterpolation::NaturalNeighbour, 500_000.).unwrap();
+    let n = POINT_GRID_SIZE;
+    let west = data.tile_data(&Tile { x: 1, y: 1, z: 2 }, 0).unwrap();
+    let east = data.tile_data(&Tile { x: 2, y: 1, z: 2 }, 0).unwrap();
+    // one more cell around the tile
+    let buffered = data.tile_data(&Tile { x: 1, y: 1, z: 2 }, TILE_SIZE / n).unwrap();
+    let same = |a: f32, b: f32| a == b || (a.is_nan() && b.is_nan());
+    for j in 0..n {
+        // the cells don't depend on the buffer
+        assert!(same(west.values[j * n + n - 1], buffered.values[(j + 1) * (n + 2) + n]));
+        // the cell east of the tile is the first cell of the next tile
+        assert!(same(buffered.values[(j + 1) * (n + 2) + n + 1], east.values[j * n]));
+    }
+    assert!(east.values.iter().any(|v| !v.is_nan()));
+}
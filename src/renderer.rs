@@ -1,10 +1,20 @@
 use std::path::Path;
 use dataset::Dataset;
-use tiledata::{TILE_SIZE};
+use source::DataSource;
+use tiledata::TileGrid;
+#[cfg(test)]
+use tiledata::{TileData, TILE_SIZE};
 use tile::Tile;
-use tiledata::TileData;
 use colormap::{ColorMap,rgba};
 use scale::{Scale,normalize};
+use classes::class_index;
+use gapfill::GapFill;
+use filter::Filter;
+use mask::Mask;
+use alpha::AlphaRamp;
+use resampling::Resampling;
+use aggregation::Aggregation;
+use pipeline::{Pipeline, check_tile_size};
 use image;
 
 /// This struct represents an image tile,
@@ -12,7 +22,9 @@ use image;
 /// an image file (PNG) from it.
 pub struct ImgTile {
     /// Array of pixel values (flattened) 
-    pub pixels: Box<[u8; 4 * TILE_SIZE * TILE_SIZE]>,
+    pub pixels: Vec<u8>,
+    /// Width and height of the image (pixels)
+    pub size: u32,
     /// Web mercator x coordinate of the tile
     pub x: u32,
     /// Web mercator y coordinate of the tile
@@ -25,19 +37,22 @@ impl ImgTile {
     pub fn save<P: AsRef<Path>>(&self, path: P) {
         let _ = image::save_buffer(
             path.as_ref(),
-            &*self.pixels, // '*' to unbox and '&' to get a &[] 
-            TILE_SIZE as u32,
-            TILE_SIZE as u32,
+            &self.pixels,
+            self.size,
+            self.size,
             image::ColorType::Rgba8,
         );
     }
 }
 
-/// Provides convenient functions to render a `Dataset` instance into `ImgTile`s
+/// Provides convenient functions to render a `Dataset` instance
+/// (or any other `DataSource`) into `ImgTile`s
 pub struct Renderer {
     color_map: ColorMap,
     scale: Scale,
-    dataset: Dataset
+    source: Box<dyn DataSource>,
+    alpha: Option<AlphaRamp>,
+    pipeline: Pipeline,
 }
 impl Renderer {
     /** Create a `Renderer` instance from a dataset.
@@ -50,15 +65,84 @@ impl Renderer {
      */
     pub fn from_dataset(dataset: Dataset, scale: Scale, color_map: ColorMap)
             -> Result<Self, String> {
+        Self::from_source(Box::new(dataset), scale, color_map)
+    }
+
+    /** Create a `Renderer` instance from any data source (eg: `PointData`).
+     *
+     * # Args
+     * * `source`: the data to render
+     * * `scale`: defines how values are mapped into the colorbar
+     * * `color_map`: a ColorMap variant, which defines the *value* => *color* mapping
+     */
+    pub fn from_source(source: Box<dyn DataSource>, scale: Scale, color_map: ColorMap)
+            -> Result<Self, String> {
         Ok(
             Self {
                 color_map: color_map,
                 scale: scale,
-                dataset: dataset
+                source,
+                alpha: None,
+                pipeline: Pipeline::new(),
             }
         )
     }
 
+    /// Fill the small gaps (NaN cells) of the dataset before rendering,
+    /// otherwise they are rendered as transparent pixels.
+    pub fn with_gap_fill(mut self, gap_fill: GapFill) -> Self {
+        self.pipeline.gap_fill = Some(gap_fill);
+        self
+    }
+
+    /// Smooth the regridded values using `filter` before rendering them
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.pipeline.filter = Some(filter);
+        self
+    }
+
+    /// Only render the pixels inside `mask`, others are left transparent
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.pipeline.mask = Some(mask);
+        self
+    }
+
+    /// Modulate the opacity of the pixels, using their value or a second variable
+    pub fn with_alpha(mut self, alpha: AlphaRamp) -> Self {
+        self.alpha = Some(alpha);
+        self
+    }
+
+    /// Choose how the data is resampled into the tile pixels (default: `Resampling::Auto`)
+    pub fn with_resampling(mut self, resampling: Resampling) -> Self {
+        self.pipeline.resampling = resampling;
+        self
+    }
+
+    /// Choose how the cells covered by a pixel are combined, when the data is denser
+    /// than the pixels (default: `Aggregation::Mean`, requires `Resampling::Auto`).
+    ///
+    /// With another resampling, or fewer cells than pixels, the cells are interpolated:
+    /// the aggregation (including `Aggregation::Conservative`) has no effect.
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.pipeline.aggregation = aggregation;
+        self
+    }
+
+    /// Set the size (in pixels) of the rendered tiles, eg: 256 (default), 512 or 1024
+    /// (a power of 2, up to `MAX_TILE_SIZE`)
+    pub fn with_tile_size(mut self, tile_size: usize) -> Result<Self, String> {
+        self.pipeline.tile_size = check_tile_size(tile_size)?;
+        Ok(self)
+    }
+
+    /// Set the number of threads used to render tile pyramids (default: 1),
+    /// see `Renderer::render_n_level_tile`
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.pipeline.threads = threads.max(1);
+        self
+    }
+
     /**
      * Returns a pixel value (RGBA) from a value, according to the 
      * renderer colormap, and the scale
@@ -74,16 +158,13 @@ impl Renderer {
         rgba
     }
 
-    fn values_to_colors(&self, values: &[[f32; TILE_SIZE]; TILE_SIZE])
-            -> Box<[u8; 4 * TILE_SIZE * TILE_SIZE]> {
-        // Build output values as a boxed array
-        // otherwise it won't fit on the stack and may trigger a stackoverflow.
-        let mut colors = Box::new([0u8; 4* TILE_SIZE * TILE_SIZE]);
+    fn values_to_colors(&self, tile: &Tile, values: &TileGrid) -> Vec<u8> {
+        let mut colors = vec![0u8; 4 * values.size * values.size];
         let mut count: usize = 0;
         // iter latitude in reverse, to fit the image X,Y orientation
-        for i_lat in (0..TILE_SIZE).rev() {
-            for i_lon in 0..TILE_SIZE {
-                let rgba = self.value_to_rgba(values[i_lat][i_lon]);
+        for row in values.rows().rev() {
+            for value in row {
+                let rgba = self.value_to_rgba(*value);
                 colors[count + 0] = rgba[0];
                 colors[count + 1] = rgba[1];
                 colors[count + 2] = rgba[2];
@@ -91,12 +172,69 @@ impl Renderer {
                 count += 4;
             }
         }
+        if let Some(ref alpha) = self.alpha {
+            alpha.apply(tile, values, &self.scale, &mut colors);
+        }
         colors
     }
 
     /// Return the value stored at (lat, lon)
     pub fn value_at_coordinates(&self, lat: f64, lon: f64) -> Result<f32,String> {
-        self.dataset.value_at_coordinates(lat, lon)
+        self.source.value_at_coordinates(lat, lon)
+    }
+
+    /**
+     * Returns the highest class reached inside a tile (None if it holds no value),
+     * the renderer must use a `Scale::Classified`.
+     *
+     * Warning services can use it to flag tiles where a threshold is exceeded:
+     * it is computed from the values of the cells overlapping the tile (and the mask),
+     * before they are regridded, so peaks are not smoothed out at low zoom levels.
+     */
+    pub fn max_class(&self, tile: &Tile) -> Result<Option<usize>, String> {
+        let thresholds = match self.scale {
+            Scale::Classified { ref thresholds } => thresholds,
+            _ => return Err("The renderer scale is not classified".into()),
+        };
+        let tile_data = self.source.tile_data(tile, 0)?;
+        // the mask, at the pixel resolution
+        let size = self.pipeline.tile_size;
+        let mask = self.pipeline.mask.as_ref().map(|mask| {
+            let mut grid = TileGrid { size, values: vec![1.; size * size] };
+            mask.apply(tile, &mut grid);
+            grid
+        });
+        let bbox = &tile_data.bbox;
+        let pixel_size = (bbox.east - bbox.west) / size as f64;
+        // index of the pixel holding the part of a cell inside the tile
+        let pixel = |edges: &[f64], min: f64, max: f64| -> Option<usize> {
+            let (start, end) = (edges[0].min(edges[1]).max(min), edges[0].max(edges[1]).min(max));
+            if start >= end {
+                return None;
+            }
+            Some((((start + end) / 2. - min) / pixel_size) as usize)
+        };
+
+        let mut max_class: Option<usize> = None;
+        let n_lon = tile_data.lon.len();
+        for (i_lat, lat_edges) in tile_data.lat_edges.windows(2).enumerate() {
+            let row = match pixel(lat_edges, bbox.south, bbox.north) {
+                Some(row) => row.min(size - 1),
+                None => continue,
+            };
+            for (i_lon, lon_edges) in tile_data.lon_edges.windows(2).enumerate() {
+                let col = match pixel(lon_edges, bbox.west, bbox.east) {
+                    Some(col) => col.min(size - 1),
+                    None => continue,
+                };
+                let value = tile_data.values[i_lat * n_lon + i_lon];
+                let masked = mask.as_ref().is_some_and(|mask| mask[row][col].is_nan());
+                if !value.is_nan() && !masked {
+                    max_class = max_class.max(Some(class_index(thresholds, value)));
+                }
+            }
+        }
+        Ok(max_class)
     }
 
     /**
@@ -105,52 +243,197 @@ impl Renderer {
      * #Details
      *
      * Extract values from the renderer dataset, 
-     * interpolate them into a TILE_SIZE * TILE_SIZE grid,
+     * interpolate them into a tile_size * tile_size grid,
      * and convert them into pixel values.
      */
     pub fn render_tile(&self, tile: &Tile) -> Result<ImgTile, String> {
-        let tile_data = self.dataset.get_tile_data(tile)?;
-        let data = tile_data.to_tile_grid();
-        let colors = self.values_to_colors(&data);
-        Ok(
-            ImgTile {
-                pixels: colors,
-                x: tile.x,
-                y: tile.y,
-                z: tile.z,
-            }
-        )
+        self.render_scaled_tile(tile, 1)
     }
 
-    /// This function render a Tildata and its `level` sub-levels into ImgTile,
-    /// by *RECURSIVELY* calling itself using `data.sub_tiledata`.
-    fn render_n_tiledata_zoom(&self, data: &TileData, level: u8) -> Vec<ImgTile> {
-        let mut imgs: Vec<ImgTile> = Vec::new();
-        imgs.push(
-            ImgTile {
-                pixels: self.values_to_colors(&data.to_tile_grid()),
-                x: data.tile.x,
-                y: data.tile.y,
-                z: data.tile.z,
-            }
-        );
-
-        if level > 0 {
-            for sub_data in data.sub_tiledata() {
-                imgs.extend(self.render_n_tiledata_zoom(&sub_data, level -1));
-            }
-        }
-        return imgs;
-
+    /**
+     * Render a Tile into an ImgTile `scale_factor` times larger (eg: 2 for @2x retina tiles),
+     * the filter is scaled accordingly, so the tiles look alike.
+     */
+    pub fn render_scaled_tile(&self, tile: &Tile, scale_factor: usize) -> Result<ImgTile, String> {
+        self.pipeline.render_tile(&[&*self.source], tile, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, &grids[0])
+        })
     }
 
     /// This function renders a tile and its `level` sub-levels into ImgTile.
     /// It only extracts values from the dataset once, and recursively renders `level` levels 
     /// of tiles using those values.
+    ///
+    /// The tiles are rendered by `self.threads` threads (see `Renderer::with_threads`),
+    /// the order of the returned ImgTiles is then unspecified.
+    ///
+    /// Every ImgTile is kept in memory: for deep pyramids, prefer `render_n_level_tile_with`.
     pub fn render_n_level_tile(&self, tile: &Tile, level: u8) -> Result<Vec<ImgTile>, String> {
-        let tile_data = self.dataset.get_tile_data(tile)?;
-        return Ok(self.render_n_tiledata_zoom(&tile_data, level));
+        self.render_n_level_scaled_tile(tile, level, 1)
+    }
+
+    /// Render a tile and its `level` sub-levels, like `render_n_level_tile`,
+    /// into ImgTiles `scale_factor` times larger (eg: 2 for @2x retina tiles).
+    pub fn render_n_level_scaled_tile(&self, tile: &Tile, level: u8, scale_factor: usize) -> Result<Vec<ImgTile>, String> {
+        let mut imgs: Vec<ImgTile> = Vec::new();
+        self.render_n_level_tile_with(tile, level, scale_factor, |img| imgs.push(img))?;
+        Ok(imgs)
+    }
+
+    /**
+     * Render a tile and its `level` sub-levels, like `render_n_level_tile`,
+     * but hands each ImgTile to `callback` as soon as it is rendered (eg: to save it)
+     * instead of collecting them. The ImgTiles are `scale_factor` times larger than the tile size.
+     *
+     * #Details
+     *
+     * The values of a tile are dropped once its sub-tiles are rendered,
+     * so the memory used doesn't grow with the number of rendered tiles.
+     * `callback` is always called from the calling thread.
+     */
+    pub fn render_n_level_tile_with<F>(&self, tile: &Tile, level: u8, scale_factor: usize, callback: F) -> Result<(), String>
+        where F: FnMut(ImgTile) {
+        self.pipeline.render_n_level_tile_with(&[&*self.source], tile, level, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, &grids[0])
+        }, callback)
+    }
+
+    /**
+     * Render the block of `metatile_size` x `metatile_size` tiles containing `tile`
+     * (eg: 8 x 8 tiles) into ImgTiles.
+     *
+     * #Details
+     *
+     * The values of the whole block (plus the pixels needed around it by the filter)
+     * are extracted and resampled at once, then sliced into tiles: it avoids the seams
+     * between tiles resampled separately, and reduces the number of reads.
+     *
+     * `metatile_size` is rounded down to a power of 2, and can't exceed the number
+     * of tiles of the zoom level, nor `MAX_METATILE_SIZE` (the block is resampled in memory).
+     * The ImgTiles are ordered row by row, from north to south. The ImgTiles are `scale_factor` times larger than the tile size.
+     */
+    pub fn render_metatile(&self, tile: &Tile, metatile_size: u32, scale_factor: usize) -> Result<Vec<ImgTile>, String> {
+        self.pipeline.render_metatile(&[&*self.source], tile, metatile_size, scale_factor, &|tile, grids| {
+            self.values_to_colors(tile, &grids[0])
+        })
     }
+}
+
+#[cfg(test)]
+/// A data source made of a single peak on a flat field, `n` x `n` cells per tile
+struct PeakSource {
+    n: usize,
+}
 
+#[cfg(test)]
+impl DataSource for PeakSource {
+    fn tile_data(&self, tile: &Tile, _buffer: usize) -> Result<TileData, String> {
+        let center = self.n / 2;
+        Ok(::tiledata::synthetic_tiledata(Tile { x: tile.x, y: tile.y, z: tile.z }, self.n, |i_lat, i_lon| {
+            if (i_lat, i_lon) == (center, center) { 10. } else { 0. }
+        }))
+    }
+
+    fn value_at_coordinates(&self, _lat: f64, _lon: f64) -> Result<f32, String> {
+        Ok(0.)
+    }
+}
+
+#[test]
+fn test_max_class_peaks() {
+    // 4 cells per pixel: the peak is averaged out by the regridding
+    let scale = Scale::Classified { thresholds: vec![5.] };
+    let renderer = Renderer::from_source(Box::new(PeakSource { n: 4 * TILE_SIZE }), scale, ColorMap::BrBG).unwrap();
+    let tile = Tile { x: 0, y: 0, z: 0 };
+    assert_eq!(renderer.max_class(&tile).unwrap(), Some(1));
+    let pipeline = &renderer.pipeline;
+    let grid = pipeline.tile_grid(&pipeline.tile_data(&*renderer.source, &tile, 0, TILE_SIZE).unwrap(), 1);
+    assert!(grid.values.iter().all(|v| *v < 5.));
+}
+
+#[test]
+fn test_scaled_tile_sizes() {
+    let source = || Box::new(PeakSource { n: 16 });
+    let scale = || Scale::Linear { min: 0., max: 10. };
+    assert!(Renderer::from_source(source(), scale(), ColorMap::BrBG).unwrap().with_tile_size(0).is_err());
+    let renderer = Renderer::from_source(source(), scale(), ColorMap::BrBG).unwrap()
+        .with_tile_size(64).unwrap();
+    let tile = Tile { x: 0, y: 0, z: 0 };
+    let imgs = renderer.render_n_level_scaled_tile(&tile, 1, 2).unwrap();
+    assert_eq!(imgs.len(), 5);
+    assert!(imgs.iter().all(|img| img.size == 128 && img.pixels.len() == 4 * 128 * 128));
+    let imgs = renderer.render_metatile(&Tile { x: 1, y: 1, z: 1 }, 2, 2).unwrap();
+    assert!(imgs.iter().all(|img| img.size == 128 && img.pixels.len() == 4 * 128 * 128));
+    assert!(renderer.render_n_level_scaled_tile(&tile, 1, 0).is_err());
+}
+
+#[cfg(test)]
+/// A data source made of a latitude and longitude gradient, sampled on a global grid
+/// of `16 << (max_z - z)` cells per tile of zoom `z` (a cell per pixel of 16 pixels tiles at zoom `max_z`)
+struct GradientSource {
+    max_z: u32,
+}
+
+#[cfg(test)]
+impl DataSource for GradientSource {
+    fn tile_data(&self, tile: &Tile, _buffer: usize) -> Result<TileData, String> {
+        let n = 16 << (self.max_z - tile.z);
+        // global indices of the tile first cells (latitude from the south)
+        let lat_offset = ((1 << tile.z) - 1 - tile.y as usize) * n;
+        let lon_offset = tile.x as usize * n;
+        Ok(::tiledata::synthetic_tiledata(Tile { x: tile.x, y: tile.y, z: tile.z }, n, |i_lat, i_lon| {
+            ((lat_offset + i_lat) * 64 + lon_offset + i_lon) as f32
+        }))
+    }
+
+    fn value_at_coordinates(&self, _lat: f64, _lon: f64) -> Result<f32, String> {
+        Ok(0.)
+    }
+}
+
+#[test]
+fn test_render_metatile() {
+    let scale = Scale::Linear { min: 0., max: 4096. };
+    let renderer = Renderer::from_source(Box::new(GradientSource { max_z: 2 }), scale, ColorMap::Grayscale).unwrap()
+        .with_tile_size(16).unwrap();
+    let imgs = renderer.render_metatile(&Tile { x: 3, y: 1, z: 2 }, 4, 1).unwrap();
+    assert_eq!(imgs.len(), 16);
+    for (k, img) in imgs.iter().enumerate() {
+        // row by row, from north to south
+        assert_eq!((img.x, img.y, img.z), (k as u32 % 4, k as u32 / 4, 2));
+        let tile = renderer.render_tile(&Tile { x: img.x, y: img.y, z: img.z }).unwrap();
+        assert!(img.pixels == tile.pixels);
+    }
+    // the metatile size is clamped to MAX_METATILE_SIZE
+    let renderer = Renderer::from_source(Box::new(GradientSource { max_z: 8 }), Scale::Linear { min: 0., max: 1. }, ColorMap::Grayscale).unwrap()
+        .with_tile_size(1).unwrap();
+    assert_eq!(renderer.render_metatile(&Tile { x: 0, y: 0, z: 8 }, 1024, 1).unwrap().len(), 256);
+}
 
+#[test]
+fn test_render_threads() {
+    use points::{PointData, PointInterpolation};
+    let points: Vec<(f64, f64, f32)> = (0..50)
+        .map(|i| ((i * 37 % 360) as f64 - 180., (i * 23 % 160) as f64 - 80., i as f32))
+        .collect();
+    let render = |threads| {
+        let source = PointData::new(&points, PointInterpolation::InverseDistance { power: 2. }, 5e6).unwrap();
+        let renderer = Renderer::from_source(Box::new(source), Scale::Linear { min: 0., max: 50. }, ColorMap::Grayscale)
+            .unwrap()
+            .with_tile_size(32).unwrap()
+            .with_threads(threads);
+        let mut imgs: Vec<(u32, u32, u32, Vec<u8>)> = renderer
+            .render_n_level_tile(&Tile { x: 0, y: 0, z: 0 }, 3)
+            .unwrap()
+            .into_iter()
+            .map(|img| (img.z, img.x, img.y, img.pixels))
+            .collect();
+        imgs.sort();
+        imgs
+    };
+    let sequential = render(1);
+    // 1 + 4 + 16 + 64 tiles
+    assert_eq!(sequential.len(), 85);
+    assert!(sequential.iter().any(|img| img.3.iter().any(|pixel| *pixel != 0)));
+    assert!(sequential == render(4));
 }
@@ -0,0 +1,98 @@
+use std::f64::consts;
+
+/// Defines how the data is resampled into the pixels of a tile.
+///
+/// Every kernel ignores missing (NaN) values, and a pixel whose nearest
+/// cell is missing stays missing, so the coastlines are not smeared.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Resampling {
+    /// Average the cells covered by each pixel when the data is denser
+    /// than the pixels, interpolate it bilinearly otherwise.
+    #[default]
+    Auto,
+    /// Value of the closest cell, shows the actual cells as blocks
+    Nearest,
+    /// Bilinear interpolation between the 4 surrounding cells
+    Bilinear,
+    /// Bicubic (Keys, a = -0.5) interpolation between the 16 surrounding cells
+    Bicubic,
+    /// Lanczos (a = 3) interpolation between the 36 surrounding cells
+    Lanczos,
+}
+
+impl Resampling {
+    /// Returns the number of cells needed on each side of a position by the kernel
+    pub fn radius(&self) -> usize {
+        match *self {
+            Resampling::Lanczos => 3,
+            Resampling::Bicubic => 2,
+            _ => 1,
+        }
+    }
+
+    /// Returns the weight of a cell located at `x` cells of the requested position
+    pub fn weight(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match *self {
+            Resampling::Bicubic => {
+                if x <= 1. {
+                    1.5 * x.powi(3) - 2.5 * x.powi(2) + 1.
+                } else if x < 2. {
+                    -0.5 * x.powi(3) + 2.5 * x.powi(2) - 4. * x + 2.
+                } else {
+                    0.
+                }
+            }
+            Resampling::Lanczos => {
+                if x == 0. {
+                    1.
+                } else if x < 3. {
+                    let pi_x = consts::PI * x;
+                    3. * pi_x.sin() * (pi_x / 3.).sin() / (pi_x * pi_x)
+                } else {
+                    0.
+                }
+            }
+            Resampling::Nearest => if x < 0.5 { 1. } else { 0. },
+            _ => (1. - x).max(0.),
+        }
+    }
+}
+
+/// Returns the (fractional) index of `value` in the sorted `coordinates`,
+/// clamped to [0; coordinates.len() - 1].
+pub fn fractional_index(coordinates: &[f64], value: f64) -> f64 {
+    let n = coordinates.len();
+    if n < 2 {
+        return 0.;
+    }
+    let ascending = coordinates[0] < coordinates[n - 1];
+    // number of coordinates before `value`
+    let i = if ascending {
+        coordinates.partition_point(|c| *c <= value)
+    } else {
+        coordinates.partition_point(|c| *c >= value)
+    };
+    if i == 0 {
+        return 0.;
+    }
+    if i == n {
+        return (n - 1) as f64;
+    }
+    let (a, b) = (coordinates[i - 1], coordinates[i]);
+    (i - 1) as f64 + (value - a) / (b - a)
+}
+
+#[test]
+fn test_resampling_kernels() {
+    for resampling in &[Resampling::Bilinear, Resampling::Bicubic, Resampling::Lanczos] {
+        assert_eq!(resampling.weight(0.), 1.);
+        assert!(resampling.weight(1.).abs() < 1e-12);
+        // the weights of a shifted position add up to 1
+        let sum: f64 = (-3..4).map(|i| resampling.weight(i as f64 - 0.3)).sum();
+        assert!((sum - 1.).abs() < 0.02, "{:?} {}", resampling, sum);
+    }
+    assert_eq!(fractional_index(&[0., 10., 20.], 15.), 1.5);
+    assert_eq!(fractional_index(&[20., 10., 0.], 15.), 0.5);
+    assert_eq!(fractional_index(&[0., 10., 20.], 30.), 2.);
+}
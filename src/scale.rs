@@ -1,3 +1,6 @@
+use stats::Statistics;
+use classes::class_index;
+
 /// Enum that describes how the
 /// transcription from a value to a color is done.
 pub enum Scale {
@@ -9,6 +12,30 @@ pub enum Scale {
     Exponential { min: f32, max: f32},
     /// Apply no transformation
     Equal,
+    /// Map each class delimited by the (ascending) thresholds to a distinct value,
+    /// to be used with a `ColorMap::Classified` (see `Classification`)
+    Classified { thresholds: Vec<f32> },
+}
+
+impl Scale {
+    /// Builds a linear scale spanning the 2% - 98% percentile range of the statistics,
+    /// so a few outliers don't flatten the colors of every other value.
+    pub fn from_statistics(statistics: &Statistics) -> Self {
+        Self::from_percentiles(statistics, 2., 98.)
+    }
+
+    /// Builds a linear scale spanning the [`lower`; `upper`] percentile range of the statistics.
+    pub fn from_percentiles(statistics: &Statistics, lower: f32, upper: f32) -> Self {
+        let (min, mut max) = statistics.percentile_range(lower, upper);
+        if min.is_nan() {
+            return Scale::Linear { min: 0., max: 1. };
+        }
+        // avoid an empty range
+        if max <= min {
+            max = min + 1.;
+        }
+        Scale::Linear { min, max }
+    }
 }
 
 #[inline] // Called once per pixel, I believe it make sense to inline it (might be wrong)
@@ -17,8 +44,8 @@ pub enum Scale {
 /// Returns a float in [0; 1]
 ///
 pub fn normalize(scale: &Scale, value: f32) -> f32 {
-    match scale {
-        &Scale::Linear{min, max} => {
+    match *scale {
+        Scale::Linear{min, max} => {
             if value < min {
                 0.
             } else if value > max {
@@ -27,7 +54,7 @@ pub fn normalize(scale: &Scale, value: f32) -> f32 {
                 (value - min) / (max - min)
             }
         },
-        &Scale::Log{min, max} => {
+        Scale::Log{min, max} => {
             if value < min {
                 0.
             } else if value > max {
@@ -36,7 +63,7 @@ pub fn normalize(scale: &Scale, value: f32) -> f32 {
                 (1. + value - min).log(10.) / (1. + max - min).log(10.)
             }
         },
-        &Scale::Exponential {min, max} => {
+        Scale::Exponential {min, max} => {
             if value <= min {
                 0.
             } else if value >= max {
@@ -45,6 +72,11 @@ pub fn normalize(scale: &Scale, value: f32) -> f32 {
                 ((value - min) / (max - min)).exp() / (1_f32).exp()
             }
         },
-        &Scale::Equal => { value },
+        Scale::Equal => { value },
+        Scale::Classified { ref thresholds } => {
+            // the center of the class interval, so it can't be mistaken for its neighbours
+            let classes = (thresholds.len() + 1) as f32;
+            (class_index(thresholds, value) as f32 + 0.5) / classes
+        },
     }
 }
@@ -0,0 +1,40 @@
+use dataset::Dataset;
+use tile::Tile;
+use tiledata::TileData;
+
+/// A provider of tile data, which can be rendered by a `Renderer`.
+///
+/// `Dataset` (gridded netCDF files) implements it, as well as
+/// `PointData` (scattered observations) and `MeshDataset` (unstructured meshes).
+///
+/// Sources are shared between the rendering threads (see `Renderer::with_threads`),
+/// hence the `Send + Sync` bounds.
+pub trait DataSource: Send + Sync {
+    /// Extract the data of a tile, plus a border of `buffer` pixels around it
+    /// (expressed in pixels of a TILE_SIZE wide tile)
+    fn tile_data(&self, tile: &Tile, buffer: usize) -> Result<TileData, String>;
+
+    /// Extract the data of a tile rendered into `size` x `size` pixels, like `tile_data`:
+    /// sources holding several resolutions (eg: `Dataset::with_overviews`) can then
+    /// return the coarsest one which still resolves the pixels.
+    fn sized_tile_data(&self, tile: &Tile, buffer: usize, _size: usize) -> Result<TileData, String> {
+        self.tile_data(tile, buffer)
+    }
+
+    /// Return the value at (lat, lon) (WGS84)
+    fn value_at_coordinates(&self, lat: f64, lon: f64) -> Result<f32, String>;
+}
+
+impl DataSource for Dataset {
+    fn tile_data(&self, tile: &Tile, buffer: usize) -> Result<TileData, String> {
+        self.get_buffered_tile_data(tile, buffer)
+    }
+
+    fn sized_tile_data(&self, tile: &Tile, buffer: usize, size: usize) -> Result<TileData, String> {
+        self.get_sized_tile_data(tile, buffer, size)
+    }
+
+    fn value_at_coordinates(&self, lat: f64, lon: f64) -> Result<f32, String> {
+        Dataset::value_at_coordinates(self, lat, lon)
+    }
+}
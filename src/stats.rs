@@ -0,0 +1,218 @@
+use std::f32;
+
+/// Statistics of the dataset cells falling inside a zone.
+///
+/// The mean and the percentiles are weighted by the area of each cell.
+#[derive(Debug)]
+pub struct ZonalStatistics {
+    /// Identifier of the zone
+    pub id: Option<String>,
+    /// Number of valid (non NaN) cells inside the zone
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    /// Area weighted mean
+    pub mean: f32,
+    /// Sum of the cell values
+    pub sum: f32,
+    /// Total area of the valid cells (square meters)
+    pub area: f64,
+    /// (percentile, value) pairs, such as (50., median)
+    pub percentiles: Vec<(f32, f32)>,
+}
+
+impl ZonalStatistics {
+    /// Computes the statistics of (value, weight) pairs,
+    /// `percentiles` must be expressed in [0; 100].
+    pub fn from_weighted_values(
+        id: Option<String>,
+        mut values: Vec<(f32, f64)>,
+        percentiles: &[f32],
+    ) -> Self {
+        values.retain(|&(value, _)| !value.is_nan());
+        values.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let mut sum: f64 = 0.;
+        let mut weighted_sum: f64 = 0.;
+        let mut area: f64 = 0.;
+        for &(value, weight) in &values {
+            sum += value as f64;
+            weighted_sum += value as f64 * weight;
+            area += weight;
+        }
+        let (min, max, mean) = if values.is_empty() {
+            (f32::NAN, f32::NAN, f32::NAN)
+        } else if area > 0. {
+            (values[0].0, values[values.len() - 1].0, (weighted_sum / area) as f32)
+        } else {
+            (values[0].0, values[values.len() - 1].0, (sum / values.len() as f64) as f32)
+        };
+        Self {
+            id,
+            count: values.len(),
+            min,
+            max,
+            mean,
+            sum: if values.is_empty() { f32::NAN } else { sum as f32 },
+            area,
+            percentiles: percentiles
+                .iter()
+                .map(|p| (*p, weighted_percentile(&values, *p)))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the `percentile` (in [0; 100]) of (value, weight) pairs
+/// **sorted** by value.
+///
+/// Returns NaN if `values` is empty.
+pub fn weighted_percentile(values: &[(f32, f64)], percentile: f32) -> f32 {
+    if values.is_empty() {
+        return f32::NAN;
+    }
+    let percentile = percentile.clamp(0., 100.);
+    let total: f64 = values.iter().map(|v| v.1).sum();
+    if total <= 0. {
+        // no weights, fall back on the nearest rank
+        let rank = (percentile / 100. * (values.len() - 1) as f32).round();
+        return values[rank as usize].0;
+    }
+    let target = total * (percentile as f64) / 100.;
+    let mut cumulated: f64 = 0.;
+    for &(value, weight) in values {
+        cumulated += weight;
+        if cumulated >= target {
+            return value;
+        }
+    }
+    values[values.len() - 1].0
+}
+
+/// Distribution of values into bins of equal width, spanning [min; max].
+#[derive(Debug)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    /// Number of values falling in each bin
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// Returns the width of a bin
+    pub fn bin_width(&self) -> f32 {
+        (self.max - self.min) / self.counts.len() as f32
+    }
+}
+
+/// Global statistics of a set of values (NaN are ignored).
+#[derive(Debug)]
+pub struct Statistics {
+    /// Number of valid (non NaN) values
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub histogram: Histogram,
+}
+
+impl Statistics {
+    /// Computes the statistics of `values`, distributed into `bins` histogram bins.
+    pub fn from_values(values: &[f32], bins: usize) -> Self {
+        let bins = bins.max(1);
+        let mut count: usize = 0;
+        let mut sum: f64 = 0.;
+        let mut min: f32 = f32::INFINITY;
+        let mut max: f32 = f32::NEG_INFINITY;
+        for value in values.iter().filter(|v| !v.is_nan()) {
+            count += 1;
+            sum += *value as f64;
+            min = min.min(*value);
+            max = max.max(*value);
+        }
+        if count == 0 {
+            min = f32::NAN;
+            max = f32::NAN;
+        }
+
+        let mut counts: Vec<usize> = vec![0; bins];
+        if count > 0 {
+            let width = (max - min) / bins as f32;
+            for value in values.iter().filter(|v| !v.is_nan()) {
+                let bin = if width > 0. {
+                    (((*value - min) / width) as usize).min(bins - 1)
+                } else {
+                    0
+                };
+                counts[bin] += 1;
+            }
+        }
+        Self {
+            count,
+            min,
+            max,
+            mean: if count == 0 { f32::NAN } else { (sum / count as f64) as f32 },
+            histogram: Histogram { min, max, counts },
+        }
+    }
+
+    /// Returns an estimation of the `percentile` (in [0; 100]) of the values,
+    /// linearly interpolated inside the histogram bins.
+    ///
+    /// Returns NaN if there is no valid value.
+    pub fn percentile(&self, percentile: f32) -> f32 {
+        if self.count == 0 {
+            return f32::NAN;
+        }
+        let target = self.count as f32 * percentile.clamp(0., 100.) / 100.;
+        let width = self.histogram.bin_width();
+        let mut cumulated: usize = 0;
+        for (bin, count) in self.histogram.counts.iter().enumerate() {
+            if *count > 0 && (cumulated + count) as f32 >= target {
+                let ratio = (target - cumulated as f32) / *count as f32;
+                return (self.min + width * (bin as f32 + ratio)).clamp(self.min, self.max);
+            }
+            cumulated += count;
+        }
+        self.max
+    }
+
+    /// Returns the range spanned by the `lower` and `upper` percentiles
+    /// (eg: `(2., 98.)`), which ignores outliers.
+    pub fn percentile_range(&self, lower: f32, upper: f32) -> (f32, f32) {
+        (self.percentile(lower), self.percentile(upper))
+    }
+}
+
+#[test]
+fn test_zonal_statistics() {
+    let values = vec![(4., 1.), (f32::NAN, 1.), (1., 1.), (2., 2.)];
+    let stats = ZonalStatistics::from_weighted_values(None, values, &[0., 50., 100.]);
+    assert_eq!(stats.count, 3);
+    assert_eq!((stats.min, stats.max, stats.sum), (1., 4., 7.));
+    assert_eq!(stats.mean, 2.25);
+    assert_eq!(stats.area, 4.);
+    assert_eq!(stats.percentiles, vec![(0., 1.), (50., 2.), (100., 4.)]);
+
+    let empty = ZonalStatistics::from_weighted_values(None, Vec::new(), &[50.]);
+    assert_eq!(empty.count, 0);
+    assert!(empty.mean.is_nan() && empty.percentiles[0].1.is_nan());
+}
+
+#[test]
+fn test_statistics() {
+    let values: Vec<f32> = (0..101).map(|v| v as f32).chain(vec![f32::NAN]).collect();
+    let stats = Statistics::from_values(&values, 100);
+    assert_eq!(stats.count, 101);
+    assert_eq!((stats.min, stats.max, stats.mean), (0., 100., 50.));
+    assert_eq!(stats.histogram.counts.iter().sum::<usize>(), 101);
+    assert_eq!(stats.histogram.counts[99], 2);
+    assert_eq!(stats.percentile(0.), 0.);
+    assert_eq!(stats.percentile(100.), 100.);
+    let (low, high) = stats.percentile_range(2., 98.);
+    assert!((low - 2.).abs() <= 1. && (high - 98.).abs() <= 1.);
+
+    let empty = Statistics::from_values(&[f32::NAN], 10);
+    assert_eq!(empty.count, 0);
+    assert!(empty.percentile(50.).is_nan());
+}
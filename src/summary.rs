@@ -0,0 +1,193 @@
+use netcdf::attribute::AttrValue;
+use tile::LonLatBbox;
+use json;
+
+/// Value of a netCDF attribute
+#[derive(Debug, PartialEq)]
+pub enum AttributeValue {
+    Text(String),
+    /// Numeric attributes, scalars are stored as a single element
+    Numbers(Vec<f64>),
+}
+
+impl AttributeValue {
+    fn to_json(&self) -> String {
+        match *self {
+            AttributeValue::Text(ref text) => json::string(text),
+            AttributeValue::Numbers(ref numbers) if numbers.len() == 1 => json::number(numbers[0]),
+            AttributeValue::Numbers(ref numbers) => json::numbers(numbers),
+        }
+    }
+}
+
+impl From<AttrValue> for AttributeValue {
+    fn from(value: AttrValue) -> Self {
+        // widen every numeric type to f64
+        fn widen<T: Into<f64>>(values: Vec<T>) -> AttributeValue {
+            AttributeValue::Numbers(values.into_iter().map(Into::into).collect())
+        }
+        match value {
+            AttrValue::Str(x) => AttributeValue::Text(x),
+            AttrValue::Uchar(x) => widen(vec![x]),
+            AttrValue::Uchars(x) => widen(x),
+            AttrValue::Schar(x) => widen(vec![x]),
+            AttrValue::Schars(x) => widen(x),
+            AttrValue::Ushort(x) => widen(vec![x]),
+            AttrValue::Ushorts(x) => widen(x),
+            AttrValue::Short(x) => widen(vec![x]),
+            AttrValue::Shorts(x) => widen(x),
+            AttrValue::Uint(x) => widen(vec![x]),
+            AttrValue::Uints(x) => widen(x),
+            AttrValue::Int(x) => widen(vec![x]),
+            AttrValue::Ints(x) => widen(x),
+            AttrValue::Ulonglong(x) => AttributeValue::Numbers(vec![x as f64]),
+            AttrValue::Ulonglongs(x) => {
+                AttributeValue::Numbers(x.into_iter().map(|v| v as f64).collect())
+            }
+            AttrValue::Longlong(x) => AttributeValue::Numbers(vec![x as f64]),
+            AttrValue::Longlongs(x) => {
+                AttributeValue::Numbers(x.into_iter().map(|v| v as f64).collect())
+            }
+            AttrValue::Float(x) => widen(vec![x]),
+            AttrValue::Floats(x) => widen(x),
+            AttrValue::Double(x) => widen(vec![x]),
+            AttrValue::Doubles(x) => widen(x),
+        }
+    }
+}
+
+/// Describes a netCDF dimension
+#[derive(Debug)]
+pub struct DimensionInfo {
+    pub name: String,
+    pub len: usize,
+    pub unlimited: bool,
+}
+
+/// Describes a netCDF variable
+#[derive(Debug)]
+pub struct VariableInfo {
+    pub name: String,
+    /// Names of the dimensions the variable is indexed by
+    pub dimensions: Vec<String>,
+    /// Length of each dimension
+    pub shape: Vec<usize>,
+    /// Name of the data type (eg: "f32")
+    pub data_type: String,
+    /// The `units` attribute
+    pub units: Option<String>,
+    /// The `long_name` attribute
+    pub long_name: Option<String>,
+    pub attributes: Vec<(String, AttributeValue)>,
+}
+
+/// Summary of the content of a netCDF file, as returned by `Dataset::inspect`.
+#[derive(Debug)]
+pub struct DatasetSummary {
+    pub dimensions: Vec<DimensionInfo>,
+    pub variables: Vec<VariableInfo>,
+    /// Global attributes
+    pub attributes: Vec<(String, AttributeValue)>,
+    /// Extent of the grid cell centers (WGS 84)
+    pub extent: LonLatBbox,
+    /// Mean grid spacing (degrees), as (longitude, latitude)
+    pub resolution: (f64, f64),
+}
+
+fn attributes_to_json(attributes: &[(String, AttributeValue)]) -> String {
+    let members: Vec<String> = attributes
+        .iter()
+        .map(|(name, value)| format!("{}:{}", json::string(name), value.to_json()))
+        .collect();
+    format!("{{{}}}", members.join(","))
+}
+
+fn optional_string(value: &Option<String>) -> String {
+    match *value {
+        Some(ref text) => json::string(text),
+        None => "null".into(),
+    }
+}
+
+impl DatasetSummary {
+    /// Returns the `VariableInfo` of a variable
+    pub fn variable(&self, name: &str) -> Option<&VariableInfo> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+
+    /// Export the summary as a JSON object
+    pub fn to_json(&self) -> String {
+        let dimensions: Vec<String> = self
+            .dimensions
+            .iter()
+            .map(|dimension| {
+                format!(
+                    "{{\"name\":{},\"len\":{},\"unlimited\":{}}}",
+                    json::string(&dimension.name),
+                    dimension.len,
+                    dimension.unlimited
+                )
+            })
+            .collect();
+        let variables: Vec<String> = self
+            .variables
+            .iter()
+            .map(|variable| {
+                let names: Vec<String> =
+                    variable.dimensions.iter().map(|name| json::string(name)).collect();
+                let shape: Vec<String> = variable.shape.iter().map(|len| len.to_string()).collect();
+                format!(
+                    "{{\"name\":{},\"dimensions\":[{}],\"shape\":[{}],\"type\":{},\"units\":{},\"long_name\":{},\"attributes\":{}}}",
+                    json::string(&variable.name),
+                    names.join(","),
+                    shape.join(","),
+                    json::string(&variable.data_type),
+                    optional_string(&variable.units),
+                    optional_string(&variable.long_name),
+                    attributes_to_json(&variable.attributes)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"dimensions\":[{}],\"variables\":[{}],\"attributes\":{},\"extent\":{{\"west\":{},\"south\":{},\"east\":{},\"north\":{}}},\"resolution\":{{\"lon\":{},\"lat\":{}}}}}",
+            dimensions.join(","),
+            variables.join(","),
+            attributes_to_json(&self.attributes),
+            json::number(self.extent.west),
+            json::number(self.extent.south),
+            json::number(self.extent.east),
+            json::number(self.extent.north),
+            json::number(self.resolution.0),
+            json::number(self.resolution.1)
+        )
+    }
+}
+
+#[test]
+fn test_summary_to_json() {
+    let summary = DatasetSummary {
+        dimensions: vec![DimensionInfo { name: "lat".into(), len: 2, unlimited: false }],
+        variables: vec![VariableInfo {
+            name: "wind".into(),
+            dimensions: vec!["lat".into()],
+            shape: vec![2],
+            data_type: "f32".into(),
+            units: Some("m s-1".into()),
+            long_name: None,
+            attributes: vec![("_FillValue".into(), AttrValue::Float(-1.).into())],
+        }],
+        attributes: vec![("title".into(), AttributeValue::Text("test".into()))],
+        extent: LonLatBbox { west: -10., south: 40., east: 10., north: 50. },
+        resolution: (0.5, 0.25),
+    };
+    assert_eq!(summary.variable("wind").map(|v| v.shape.clone()), Some(vec![2]));
+    assert_eq!(
+        summary.to_json(),
+        "{\"dimensions\":[{\"name\":\"lat\",\"len\":2,\"unlimited\":false}],\
+         \"variables\":[{\"name\":\"wind\",\"dimensions\":[\"lat\"],\"shape\":[2],\"type\":\"f32\",\
+         \"units\":\"m s-1\",\"long_name\":null,\"attributes\":{\"_FillValue\":-1}}],\
+         \"attributes\":{\"title\":\"test\"},\
+         \"extent\":{\"west\":-10,\"south\":40,\"east\":10,\"north\":50},\
+         \"resolution\":{\"lon\":0.5,\"lat\":0.25}}"
+    );
+}
@@ -1,4 +1,5 @@
 use std::f64::{self, consts};
+use tiledata::TILE_SIZE;
 
 const EARTH_RADIUS: f64 = 6378137.0;
 const PERIMETER: f64 = EARTH_RADIUS * 2. *  consts::PI;
@@ -60,6 +61,29 @@ pub fn wgs84_to_meters(lon: f64, lat: f64) -> (f64, f64){
     (lon_wgs84_to_meters(lon), lat_wgs84_to_meters(lat))
 }
 
+/**
+ * Turns a longitude expressed in meters (Spherical mercator) into WGS84
+ */
+pub fn lon_meters_to_wgs84(x: f64) -> f64 {
+    (x / EARTH_RADIUS).to_degrees()
+}
+/**
+ * Turns a latitude expressed in meters (Spherical mercator) into WGS84
+ */
+pub fn lat_meters_to_wgs84(y: f64) -> f64 {
+    (y / EARTH_RADIUS).tanh().asin().to_degrees()
+}
+
+/**
+ * Returns the great-circle distance (meters) between two WGS84 points
+ */
+pub fn haversine_distance(lon_a: f64, lat_a: f64, lon_b: f64, lat_b: f64) -> f64 {
+    let d_lat = (lat_b - lat_a).to_radians();
+    let d_lon = (lon_b - lon_a).to_radians();
+    let a = (d_lat / 2.).sin().powi(2)
+        + lat_a.to_radians().cos() * lat_b.to_radians().cos() * (d_lon / 2.).sin().powi(2);
+    2. * EARTH_RADIUS * a.sqrt().asin()
+}
 
 #[derive(Debug, PartialEq)]
 pub struct LonLatBbox {
@@ -78,6 +102,13 @@ impl LonLatBbox {
         let (east, south) = wgs84_to_meters(self.east, self.south);
         Bbox {west, south, east, north}
     }
+
+    /// Returns the area of the bounding box on the sphere (square meters)
+    pub fn area(&self) -> f64 {
+        let d_lon = (self.east - self.west).abs().to_radians();
+        let d_sin = (self.north.to_radians().sin() - self.south.to_radians().sin()).abs();
+        EARTH_RADIUS * EARTH_RADIUS * d_lon * d_sin
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -91,6 +122,22 @@ pub struct Bbox {
     /// meters
     pub north: f64,
 }
+impl Bbox {
+    /**
+     * Returns the bounding box extended by `buffer` pixels on each side,
+     * considering that self spans TILE_SIZE pixels.
+     */
+    pub fn buffered(&self, buffer: usize) -> Bbox {
+        let x_buffer = (self.east - self.west).abs() * buffer as f64 / TILE_SIZE as f64;
+        let y_buffer = (self.north - self.south).abs() * buffer as f64 / TILE_SIZE as f64;
+        Bbox {
+            west: self.west - x_buffer,
+            south: self.south - y_buffer,
+            east: self.east + x_buffer,
+            north: self.north + y_buffer,
+        }
+    }
+}
 
 /// This struct holds basic informations about a Tile.
 #[derive(Debug, PartialEq)]
@@ -153,3 +200,17 @@ fn test_bounds() {
     };
     assert_eq!(bbox, tile.bounds());
 }
+
+#[test]
+fn test_meters_to_wgs84() {
+    let (x, y) = wgs84_to_meters(-9.140625, 53.33087298301705);
+    assert!((lon_meters_to_wgs84(x) - -9.140625).abs() < 1e-9);
+    assert!((lat_meters_to_wgs84(y) - 53.33087298301705).abs() < 1e-9);
+}
+
+#[test]
+fn test_bbox_area() {
+    let globe = LonLatBbox { west: -180., south: -90., east: 180., north: 90. };
+    let sphere = 4. * consts::PI * EARTH_RADIUS * EARTH_RADIUS;
+    assert!((globe.area() - sphere).abs() / sphere < 1e-12);
+}
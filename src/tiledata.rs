@@ -1,10 +1,67 @@
-use tile::{Tile,Bbox};
-use std::f32;
+use tile::{Tile,Bbox,lat_meters_to_wgs84};
+use std::{f32, f64};
+use std::ops::{Index, IndexMut};
+use std::slice::{Chunks, ChunksMut};
 use utils::{search_closest_idx_below,search_closest_idx_over};
+use gapfill::{GapFill,fill_gaps};
+use filter::{Filter,apply_filter};
+use resampling::{Resampling,fractional_index};
+use aggregation::Aggregation;
 
+/// Default size (in pixels) of the tiles
 pub const TILE_SIZE: usize = 256;
 
-/// Holds data and provides methods to regrid data into a 256 x 256 grid.
+/// A square grid of pixel values, regridded from a `TileData`.
+///
+/// Rows go from south to north, `grid[i_lat][i_lon]` returns the value of a pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileGrid {
+    /// Number of pixels on each side
+    pub size: usize,
+    /// Flattened array (lat, lon), starting from the south west corner
+    pub values: Vec<f32>,
+}
+
+impl TileGrid {
+    /// Creates a grid of `size` x `size` missing (NaN) values
+    pub fn new(size: usize) -> Self {
+        Self { size, values: vec![f32::NAN; size * size] }
+    }
+
+    /// Iterates over the rows, from south to north
+    pub fn rows(&self) -> Chunks<'_, f32> {
+        self.values.chunks(self.size)
+    }
+
+    pub fn rows_mut(&mut self) -> ChunksMut<'_, f32> {
+        self.values.chunks_mut(self.size)
+    }
+
+    /// Extract the `size` x `size` grid whose south west pixel is (i_lat, i_lon)
+    pub fn crop(&self, i_lat: usize, i_lon: usize, size: usize) -> TileGrid {
+        let mut grid = TileGrid::new(size);
+        for (row, values) in grid.rows_mut().zip(self.rows().skip(i_lat)) {
+            row.copy_from_slice(&values[i_lon..(i_lon + size)]);
+        }
+        grid
+    }
+}
+
+impl Index<usize> for TileGrid {
+    type Output = [f32];
+
+    fn index(&self, i_lat: usize) -> &[f32] {
+        &self.values[(i_lat * self.size)..((i_lat + 1) * self.size)]
+    }
+}
+
+impl IndexMut<usize> for TileGrid {
+    fn index_mut(&mut self, i_lat: usize) -> &mut [f32] {
+        &mut self.values[(i_lat * self.size)..((i_lat + 1) * self.size)]
+    }
+}
+
+/// Holds data and provides methods to regrid data into a square grid of pixels.
 #[derive(Debug)]
 pub struct TileData {
     /// Must be expressed in meters, in ascending order
@@ -15,6 +72,11 @@ pub struct TileData {
     pub lon: Vec<f64>,
     pub min_lon: f64,
     pub max_lon: f64,
+    /// Edges of the latitude cells (`lat.len() + 1` values, meters),
+    /// see `utils::cell_edges` when the data has no explicit bounds
+    pub lat_edges: Vec<f64>,
+    /// Edges of the longitude cells (`lon.len() + 1` values, meters)
+    pub lon_edges: Vec<f64>,
     /// Values must be a flattened array (lat, lon)
     pub values: Vec<f32>,
     pub bbox: Bbox,
@@ -27,41 +89,110 @@ impl TileData {
      * regrid self.values into a TILE_SIZE x TILE_SIZE grid.
      * 
      */
-    pub fn to_tile_grid(&self) -> Box<[[f32; TILE_SIZE]; TILE_SIZE]> {
+    pub fn to_tile_grid(&self) -> TileGrid {
+        self.to_tile_grid_with(TILE_SIZE, &Resampling::Auto, &Aggregation::Mean, None)
+    }
+
+    /**
+     * regrid self.values into a TILE_SIZE x TILE_SIZE grid, and smooth it using `filter`.
+     *
+     * The pixels around the tile are regridded and filtered as well,
+     * so filtered tiles stay seamless (as long as self holds data around the tile,
+     * see `Dataset::get_buffered_tile_data`).
+     */
+    pub fn to_filtered_tile_grid(&self, filter: &Filter) -> TileGrid {
+        self.to_tile_grid_with(TILE_SIZE, &Resampling::Auto, &Aggregation::Mean, Some(filter))
+    }
+
+    /**
+     * regrid self.values into a `size` x `size` grid using the `resampling` kernel,
+     * and smooth it using `filter` (if any, see `to_filtered_tile_grid`).
+     *
+     * `aggregation` combines the cells covered by each pixel when the data is denser
+     * than the pixels (only with `Resampling::Auto`).
+     */
+    pub fn to_tile_grid_with(
+        &self,
+        size: usize,
+        resampling: &Resampling,
+        aggregation: &Aggregation,
+        filter: Option<&Filter>,
+    ) -> TileGrid {
+        match filter {
+            Some(filter) => {
+                let buffer = filter.radius();
+                let buffered_size = size + 2 * buffer;
+                let values = apply_filter(
+                    &self.regrid(size, buffer, resampling, aggregation),
+                    buffered_size,
+                    buffered_size,
+                    filter,
+                );
+                crop_tile_grid(&values, size, buffer)
+            }
+            None => crop_tile_grid(&self.regrid(size, 0, resampling, aggregation), size, 0),
+        }
+    }
+
+    /**
+     * regrid self.values into a (tile_size + 2 * buffer) square grid,
+     * covering the tile (`tile_size` pixels wide) and `buffer` pixels around it.
+     *
+     * Returns a flattened array (lat, lon), starting from the south west corner.
+     */
+    fn regrid(
+        &self,
+        tile_size: usize,
+        buffer: usize,
+        resampling: &Resampling,
+        aggregation: &Aggregation,
+    ) -> Vec<f32> {
+        let size = tile_size + 2 * buffer;
 
         // Build latitude needed for each pixel
-        let lat_inc: f64 = (self.bbox.north -self.bbox.south).abs() / (TILE_SIZE as f64);
-        let lats: Vec<f64> = (0..TILE_SIZE).map(|i| {
-            self.bbox.south + lat_inc * (0.5 + i as f64)
+        let lat_inc: f64 = (self.bbox.north -self.bbox.south).abs() / (tile_size as f64);
+        let lats: Vec<f64> = (0..size).map(|i| {
+            self.bbox.south + lat_inc * (0.5 + i as f64 - buffer as f64)
         }).collect();
 
         // Build longitude needed for each pixel
-        let lon_inc: f64 = (self.bbox.east - self.bbox.west).abs() / (TILE_SIZE as f64);
-        let lons: Vec<f64> = (0..TILE_SIZE).map(|i| {
-            self.bbox.west + lon_inc * (0.5 + i as f64)
+        let lon_inc: f64 = (self.bbox.east - self.bbox.west).abs() / (tile_size as f64);
+        let lons: Vec<f64> = (0..size).map(|i| {
+            self.bbox.west + lon_inc * (0.5 + i as f64 - buffer as f64)
         }).collect();
 
-        let lat_min: f64 = self.min_lat - 0.5 * lat_inc;
-        let lat_max: f64 = self.max_lat + 0.5 * lat_inc;
-        let lon_min: f64 = self.min_lon - 0.5 * lon_inc;
-        let lon_max: f64 = self.max_lon + 0.5 * lon_inc;
+        let (lat_min, lat_max) = edges_range(&self.lat_edges);
+        let (lon_min, lon_max) = edges_range(&self.lon_edges);
         // this closure returns true if the lon/lat coordinates 
-        // are included in the dataset
+        // are included in the dataset cells
         let in_value_extend = | lat: f64, lon: f64 | -> bool {
             lat >= lat_min && lat <= lat_max && lon >= lon_min && lon <= lon_max
         };
 
-        // Build output values as a boxed array
-        // otherwise it won't fit on the stack and may trigger a stackoverflow.
-        let mut values = Box::new([[f32::NAN; TILE_SIZE]; TILE_SIZE]);
-        // directly average the nearest data or interpole it
+        let mut values = vec![f32::NAN; size * size];
+        // With the Auto resampling, directly aggregate the nearest data or interpole it
         // depending of the number of data available
-        if self.values.len() > TILE_SIZE * TILE_SIZE {
-            // average the data contained in the pixel extend
+        let aggregate = match *resampling {
+            Resampling::Auto => self.values.len() > tile_size * tile_size,
+            _ => false,
+        };
+        if aggregate && *aggregation == Aggregation::Conservative {
+            // weight the data by its overlap with the pixel extend
             for (i_lat, lat) in lats.iter().enumerate() {
+                let lat_weights = overlaps(&self.lat_edges, *lat - lat_inc / 2., *lat + lat_inc / 2., true);
                 for (i_lon, lon) in lons.iter().enumerate() {
                     if in_value_extend(*lat, *lon) {
-                        values[i_lat][i_lon] = self.resample_average(*lat, *lon, lat_inc, lon_inc);
+                        let lon_weights = overlaps(&self.lon_edges, *lon - lon_inc / 2., *lon + lon_inc / 2., false);
+                        values[i_lat * size + i_lon] = self.weighted_mean(&lat_weights, &lon_weights);
+                    }
+                }
+            }
+        } else if aggregate {
+            // aggregate the data contained in the pixel extend
+            for (i_lat, lat) in lats.iter().enumerate() {
+                for (i_lon, lon) in lons.iter().enumerate() {
+                    if in_value_extend(*lat, *lon) {
+                        values[i_lat * size + i_lon] = self.resample_aggregate(*lat, *lon, lat_inc, lon_inc, aggregation);
                     }
                 }
             }
@@ -70,7 +201,12 @@ impl TileData {
             for (i_lat, lat) in lats.iter().enumerate() {
                 for (i_lon, lon) in lons.iter().enumerate() {
                     if in_value_extend(*lat, *lon) {
-                        values[i_lat][i_lon] = self.interpolate_value_at(*lat, *lon);
+                        values[i_lat * size + i_lon] = match *resampling {
+                            Resampling::Auto | Resampling::Bilinear => {
+                                self.interpolate_value_at(*lat, *lon)
+                            },
+                            _ => self.kernel_value_at(*lat, *lon, resampling),
+                        };
                     }
                 }
             }
@@ -78,8 +214,15 @@ impl TileData {
         values
     }
 
-    /// Fetch and compute the average of all value represented by a single pixel
-    fn resample_average(&self, requested_lat: f64, requested_lon: f64, lat_inc: f64, lon_inc: f64) ->  f32 {
+    /// Fetch and aggregate all the values represented by a single pixel
+    fn resample_aggregate(
+        &self,
+        requested_lat: f64,
+        requested_lon: f64,
+        lat_inc: f64,
+        lon_inc: f64,
+        aggregation: &Aggregation,
+    ) -> f32 {
 
         // get the index ot the lowest bound
         let mut min_lat_idx = search_closest_idx_below(
@@ -133,26 +276,71 @@ impl TileData {
                 values.push(self.value_at(lat_idx, lon_idx));
             }
         }
-        // compute the average of it
-        let mut pixel_value: f32 = 0.;
-        let mut valid_count: f32 = 0.;
-        for value in values {
-            // ignore NAN
-            if !value.is_nan() {
-                pixel_value += value;
-                valid_count += 1.;
+        aggregation.aggregate(&mut values)
+    }
+
+    /// Mean of the values weighted by the product of their latitude and longitude weights
+    fn weighted_mean(&self, lat_weights: &[(usize, f64)], lon_weights: &[(usize, f64)]) -> f32 {
+        let mut sum: f64 = 0.;
+        let mut weights: f64 = 0.;
+        for &(i_lat, lat_weight) in lat_weights {
+            for &(i_lon, lon_weight) in lon_weights {
+                let value = self.value_at(i_lat, i_lon);
+                if !value.is_nan() {
+                    sum += value as f64 * lat_weight * lon_weight;
+                    weights += lat_weight * lon_weight;
+                }
+            }
+        }
+        if weights > 0. { (sum / weights) as f32 } else { f32::NAN }
+    }
+
+    /// Resample the data at the requested lat / lon, as the sum of the surrounding cells
+    /// weighted by the `resampling` kernel (missing cells are ignored).
+    fn kernel_value_at(&self, requested_lat: f64, requested_lon: f64, resampling: &Resampling) -> f32 {
+        let lat_idx = fractional_index(&self.lat, requested_lat);
+        let lon_idx = fractional_index(&self.lon, requested_lon);
+        // keep the pixels whose closest cell is missing empty
+        let nearest = self.value_at(lat_idx.round() as usize, lon_idx.round() as usize);
+        if nearest.is_nan() || *resampling == Resampling::Nearest {
+            return nearest;
+        }
+        let radius = resampling.radius() as isize;
+        let (lat_start, lon_start) = (lat_idx.floor() as isize, lon_idx.floor() as isize);
+        let mut sum: f64 = 0.;
+        let mut weights: f64 = 0.;
+        for i_lat in (lat_start - radius + 1)..(lat_start + radius + 1) {
+            if i_lat < 0 || i_lat >= self.lat.len() as isize {
+                continue;
+            }
+            let lat_weight = resampling.weight(lat_idx - i_lat as f64);
+            for i_lon in (lon_start - radius + 1)..(lon_start + radius + 1) {
+                if i_lon < 0 || i_lon >= self.lon.len() as isize {
+                    continue;
+                }
+                let value = self.value_at(i_lat as usize, i_lon as usize);
+                if value.is_nan() {
+                    continue;
+                }
+                let weight = lat_weight * resampling.weight(lon_idx - i_lon as f64);
+                sum += weight * value as f64;
+                weights += weight;
             }
         }
-        if valid_count == 0. {
-            return f32::NAN;
+        // the negative lobes of the kernel may cancel the weights out
+        if weights < 0.1 {
+            return nearest;
         }
-        pixel_value / valid_count
+        (sum / weights) as f32
     }
 
     /// This function fetch and interpolate the data from self.value, self.lon, self.lat
     /// at the requested lat / lon.
     /// It basically performs a bilinear interpolation
     fn interpolate_value_at(&self, requested_lat: f64, requested_lon: f64) ->  f32 {
+        // don't extrapolate between the outer cell centers and edges
+        let requested_lat = requested_lat.max(self.min_lat).min(self.max_lat);
+        let requested_lon = requested_lon.max(self.min_lon).min(self.max_lon);
         // fetch nearest longitude / latitude indices
         let lat_idx = search_closest_idx_below(&self.lat, requested_lat).unwrap();
         let lon_idx = search_closest_idx_below(&self.lon, requested_lon).unwrap();
@@ -242,6 +430,22 @@ impl TileData {
         }
     }
 
+    /// Size (meters) of the largest cell, along the latitude or the longitude
+    pub fn max_cell_size(&self) -> f64 {
+        self.lat_edges
+            .windows(2)
+            .chain(self.lon_edges.windows(2))
+            .map(|edges| (edges[1] - edges[0]).abs())
+            .fold(0., f64::max)
+    }
+
+    /// Estimate the missing (NaN) values from their valid neighbours,
+    /// gaps larger than `gap_fill.max_gap_size` cells are left untouched.
+    pub fn fill_gaps(&mut self, gap_fill: &GapFill) {
+        let (width, height) = (self.lon.len(), self.lat.len());
+        fill_gaps(&mut self.values, width, height, gap_fill);
+    }
+
     #[inline]
     /// Return the value of self.values as if it was a bi-dimensional array.
     fn value_at(&self, lat_idx: usize, lon_idx: usize) -> f32 {
@@ -250,6 +454,12 @@ impl TileData {
 
     /// Creates up to 4 tiles, representing the n+1 zoom level using self.values
     pub fn sub_tiledata(&self) -> Vec<Self> {
+        self.buffered_sub_tiledata(0)
+    }
+
+    /// Creates up to 4 tiles, representing the n+1 zoom level using self.values,
+    /// each of them holding (when available) `buffer` pixels of data around the tile.
+    pub fn buffered_sub_tiledata(&self, buffer: usize) -> Vec<Self> {
         // TODO: Use binary search
         let mut sub_tiledata: Vec<Self> = Vec::new();
         let base_x = self.tile.x * 2;
@@ -261,14 +471,17 @@ impl TileData {
                 let xy = tile.xy_bounds();
 
                 // search closest indices
-                let i_lat_min = search_closest_idx_below(&self.lat, xy.south).unwrap();
-                let i_lat_max = search_closest_idx_over(&self.lat, xy.north).unwrap();
-                let i_lon_min = search_closest_idx_below(&self.lon, xy.west).unwrap();
-                let i_lon_max = search_closest_idx_over(&self.lon, xy.east).unwrap();
+                let window = xy.buffered(buffer);
+                let i_lat_min = search_closest_idx_below(&self.lat, window.south).unwrap();
+                let i_lat_max = search_closest_idx_over(&self.lat, window.north).unwrap();
+                let i_lon_min = search_closest_idx_below(&self.lon, window.west).unwrap();
+                let i_lon_max = search_closest_idx_over(&self.lon, window.east).unwrap();
 
                 // Extract lat, lon and values using the computed indices
                 let subset_lat: Vec<f64> = self.lat[i_lat_min..(i_lat_max +1)].to_vec();
                 let subset_lon: Vec<f64> = self.lon[i_lon_min..(i_lon_max +1)].to_vec();
+                let subset_lat_edges: Vec<f64> = self.lat_edges[i_lat_min..(i_lat_max +2)].to_vec();
+                let subset_lon_edges: Vec<f64> = self.lon_edges[i_lon_min..(i_lon_max +2)].to_vec();
                 let mut subset_values: Vec<f32> = Vec::with_capacity(subset_lat.len() * subset_lon.len());
 
                 for i_lat in i_lat_min..(i_lat_max +1) {
@@ -285,6 +498,8 @@ impl TileData {
                         min_lat: subset_lat[0].min(subset_lat[subset_lat.len() -1]),
                         max_lat: subset_lat[0].max(subset_lat[subset_lat.len() -1]),
                         lat: subset_lat,
+                        lat_edges: subset_lat_edges,
+                        lon_edges: subset_lon_edges,
                         values: subset_values,
                         bbox: xy,
                         tile: tile
@@ -295,3 +510,199 @@ impl TileData {
         sub_tiledata
     }
 }
+
+/// Returns the (min, max) extent of cell edges
+fn edges_range(edges: &[f64]) -> (f64, f64) {
+    match (edges.first(), edges.last()) {
+        (Some(first), Some(last)) => (first.min(*last), first.max(*last)),
+        _ => (f64::NAN, f64::NAN),
+    }
+}
+
+/// Returns the indices of the cells overlapping [min; max] and the size of their overlap,
+/// `edges` being the cell edges (see `utils::cell_edges`), expressed in meters.
+///
+/// Latitude overlaps are measured on the sphere (ie: the difference of the sine of their bounds),
+/// to undo the Mercator distortion.
+fn overlaps(edges: &[f64], min: f64, max: f64, latitude: bool) -> Vec<(usize, f64)> {
+    if edges.len() < 2 {
+        return Vec::new();
+    }
+    let (a, b) = (fractional_index(edges, min), fractional_index(edges, max));
+    let first = a.min(b).floor() as usize;
+    let last = (a.max(b).ceil() as usize).min(edges.len() - 1);
+    let mut overlaps: Vec<(usize, f64)> = Vec::new();
+    for i in first..last {
+        let lower = edges[i].min(edges[i + 1]).max(min);
+        let upper = edges[i].max(edges[i + 1]).min(max);
+        if upper <= lower {
+            continue;
+        }
+        let overlap = if latitude {
+            lat_meters_to_wgs84(upper).to_radians().sin() - lat_meters_to_wgs84(lower).to_radians().sin()
+        } else {
+            upper - lower
+        };
+        overlaps.push((i, overlap));
+    }
+    overlaps
+}
+
+/// Extract the `tile_size` x `tile_size` values at the center of a flattened
+/// (tile_size + 2 * buffer) square grid.
+fn crop_tile_grid(values: &[f32], tile_size: usize, buffer: usize) -> TileGrid {
+    let size = tile_size + 2 * buffer;
+    let mut grid = TileGrid::new(tile_size);
+    for (i_lat, row) in grid.rows_mut().enumerate() {
+        let start = (i_lat + buffer) * size + buffer;
+        row.copy_from_slice(&values[start..(start + tile_size)]);
+    }
+    grid
+}
+
+#[cfg(test)]
+/// Builds a TileData covering `tile`, holding `n` x `n` values computed by `f(i_lat, i_lon)`
+pub(crate) fn synthetic_tiledata<F: Fn(usize, usize) -> f32>(tile: Tile, n: usize, f: F) -> TileData {
+    let bbox = tile.xy_bounds();
+    let lat: Vec<f64> = (0..n).map(|i| {
+        bbox.south + (bbox.north - bbox.south) * (i as f64 + 0.5) / n as f64
+    }).collect();
+    let lon: Vec<f64> = (0..n).map(|i| {
+        bbox.west + (bbox.east - bbox.west) * (i as f64 + 0.5) / n as f64
+    }).collect();
+    let mut values: Vec<f32> = Vec::with_capacity(n * n);
+    for i_lat in 0..n {
+        for i_lon in 0..n {
+            values.push(f(i_lat, i_lon));
+        }
+    }
+    TileData {
+        lat_edges: ::utils::cell_edges(&lat),
+        lon_edges: ::utils::cell_edges(&lon),
+        min_lat: lat[0],
+        max_lat: lat[n - 1],
+        lat,
+        min_lon: lon[0],
+        max_lon: lon[n - 1],
+        lon,
+        values,
+        bbox,
+        tile,
+    }
+}
+
+#[test]
+fn test_filtered_tile_grid() {
+    let data = synthetic_tiledata(Tile { x: 0, y: 0, z: 0 }, 16, |_, _| 3.);
+    let grid = data.to_filtered_tile_grid(&Filter::Gaussian { sigma: 2. });
+    assert!((grid[TILE_SIZE / 2][TILE_SIZE / 2] - 3.).abs() < 1e-5);
+    assert!((grid[20][TILE_SIZE - 20] - 3.).abs() < 1e-5);
+}
+
+#[test]
+fn test_filtered_tiles_seams() {
+    let data = synthetic_tiledata(Tile { x: 0, y: 0, z: 0 }, 64, |i_lat, i_lon| ((7 * i_lat + 3 * i_lon) % 5) as f32);
+    let filter = Filter::Gaussian { sigma: 2. };
+    // the 4 tiles of zoom 1, as a single grid
+    let whole = data.to_tile_grid_with(2 * TILE_SIZE, &Resampling::Auto, &Aggregation::Mean, Some(&filter));
+    let tiles = data.buffered_sub_tiledata(filter.radius());
+    assert_eq!((tiles[0].tile.x, tiles[0].tile.y, tiles[2].tile.x, tiles[2].tile.y), (0, 0, 1, 0));
+    let (west, east) = (tiles[0].to_filtered_tile_grid(&filter), tiles[2].to_filtered_tile_grid(&filter));
+    // both sides of the edge between the (0, 0) and (1, 0) tiles
+    for row in 0..TILE_SIZE {
+        let whole_row = &whole[TILE_SIZE + row];
+        assert!((west[row][TILE_SIZE - 1] - whole_row[TILE_SIZE - 1]).abs() < 1e-4);
+        assert!((east[row][0] - whole_row[TILE_SIZE]).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn test_resampling_kernels() {
+    // a linear gradient, with a missing cell
+    let data = synthetic_tiledata(Tile { x: 0, y: 0, z: 0 }, 8, |i_lat, i_lon| {
+        if (i_lat, i_lon) == (1, 6) { f32::NAN } else { i_lon as f32 }
+    });
+    let nearest = data.to_tile_grid_with(TILE_SIZE, &Resampling::Nearest, &Aggregation::Mean, None);
+    assert!(nearest[TILE_SIZE / 2].iter().all(|v| v.is_nan() || v.fract() == 0.));
+    for resampling in &[Resampling::Bilinear, Resampling::Bicubic, Resampling::Lanczos] {
+        let grid = data.to_tile_grid_with(TILE_SIZE, resampling, &Aggregation::Mean, None);
+        // the center of the cell #4 lies on the pixel #144 (half a pixel off)
+        assert!((grid[TILE_SIZE / 2][144] - 4.).abs() < 0.02, "{:?}", resampling);
+        assert!(grid[TILE_SIZE / 8 + 16][6 * TILE_SIZE / 8 + 16].is_nan());
+    }
+}
+
+#[test]
+fn test_conservative_aggregation() {
+    // 1.5 cells per pixel
+    let data = synthetic_tiledata(Tile { x: 0, y: 0, z: 0 }, 384, |_, i_lon| (i_lon % 3) as f32);
+    let grid = data.to_tile_grid_with(TILE_SIZE, &Resampling::Auto, &Aggregation::Conservative, None);
+    let row = &grid[TILE_SIZE / 2];
+    assert!((row[0] - 1. / 3.).abs() < 1e-4);
+    assert!((row[1] - 5. / 3.).abs() < 1e-4);
+    // the mean of the cells is kept
+    let mean = row.iter().sum::<f32>() / TILE_SIZE as f32;
+    assert!((mean - 1.).abs() < 1e-4);
+}
+
+#[test]
+fn test_conservative_aggregation_latitude() {
+    // 1.5 cells per pixel, varying along the latitude
+    let data = synthetic_tiledata(Tile { x: 0, y: 0, z: 0 }, 384, |i_lat, _| i_lat as f32);
+    // integral of the values over the sphere (per unit of longitude)
+    let area = |south: f64, north: f64| {
+        lat_meters_to_wgs84(north).to_radians().sin() - lat_meters_to_wgs84(south).to_radians().sin()
+    };
+    let integral = |values: &[f32], edges: &[f64]| -> f64 {
+        values.iter().zip(edges.windows(2)).map(|(v, e)| *v as f64 * area(e[0], e[1])).sum()
+    };
+    let column = |grid: &TileGrid| -> Vec<f32> { grid.rows().map(|row| row[0]).collect() };
+    let pixel_edges: Vec<f64> = (0..(TILE_SIZE + 1))
+        .map(|i| data.bbox.south + (data.bbox.north - data.bbox.south) * i as f64 / TILE_SIZE as f64)
+        .collect();
+    let cells: Vec<f32> = (0..384).map(|i| i as f32).collect();
+    let expected = integral(&cells, &data.lat_edges);
+
+    let conservative = data.to_tile_grid_with(TILE_SIZE, &Resampling::Auto, &Aggregation::Conservative, None);
+    let conserved = integral(&column(&conservative), &pixel_edges);
+    assert!((conserved - expected).abs() < 1e-4 * expected.abs());
+    // the first pixel covers the first cell, and half of the second one:
+    // near the pole, the second one covers a larger area of the sphere
+    let e = &data.lat_edges;
+    let half = area(e[1], (e[1] + e[2]) / 2.);
+    let weighted = half / (area(e[0], e[1]) + half);
+    assert!((column(&conservative)[0] as f64 - weighted).abs() < 1e-5);
+    assert!((weighted - 1. / 3.).abs() > 1e-3);
+}
+
+#[test]
+fn test_cell_edges_coverage() {
+    let mut data = synthetic_tiledata(Tile { x: 0, y: 0, z: 0 }, 4, |_, _| 1.);
+    // the cells cover the whole tile
+    let grid = data.to_tile_grid();
+    assert!(grid.values.iter().all(|v| *v == 1.));
+    // the last column of cells ends at its center
+    data.lon_edges[4] = data.lon[3];
+    let grid = data.to_tile_grid();
+    assert_eq!(grid[TILE_SIZE / 2][7 * TILE_SIZE / 8 - 1], 1.);
+    assert!(grid[TILE_SIZE / 2][7 * TILE_SIZE / 8 + 1].is_nan());
+}
+
+#[test]
+fn test_tile_grid_size() {
+    let data = synthetic_tiledata(Tile { x: 0, y: 0, z: 0 }, 16, |i_lat, _| i_lat as f32);
+    let grid = data.to_tile_grid_with(512, &Resampling::Nearest, &Aggregation::Mean, None);
+    assert_eq!(grid.size, 512);
+    assert_eq!(grid.values.len(), 512 * 512);
+    // 32 pixels per cell
+    assert_eq!(grid[31][100], 0.);
+    assert_eq!(grid[32][100], 1.);
+    assert_eq!(grid[511][0], 15.);
+}
+
+#[test]
+fn test_tile_grid_crop() {
+    let grid = TileGrid { size: 4, values: (0..16).map(|v| v as f32).collect() };
+    let cropped = grid.crop(2, 1, 2);
+    assert_eq!(cropped.values, vec![9., 10., 13., 14.]);
+}
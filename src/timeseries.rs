@@ -0,0 +1,146 @@
+use regex::Regex;
+use json;
+
+/// Holds the values of one or several variables along the time dimension,
+/// at a single location (think meteogram).
+#[derive(Debug)]
+pub struct TimeSeries {
+    /// Longitude of the grid point the values were read at (WGS 84)
+    pub lon: f64,
+    /// Latitude of the grid point the values were read at (WGS 84)
+    pub lat: f64,
+    /// Unix timestamps (seconds)
+    pub timestamps: Vec<i64>,
+    /// Variable names, in the same order as `values`
+    pub variables: Vec<String>,
+    /// One vector of values per variable, each of them aligned with `timestamps`
+    pub values: Vec<Vec<f32>>,
+}
+
+impl TimeSeries {
+    /// Returns the (timestamp, value) pairs of a single variable
+    pub fn pairs(&self, variable: &str) -> Option<Vec<(i64, f32)>> {
+        let idx = self.variables.iter().position(|name| name == variable)?;
+        Some(
+            self.timestamps
+                .iter()
+                .cloned()
+                .zip(self.values[idx].iter().cloned())
+                .collect(),
+        )
+    }
+
+    /// Export the time series as CSV, with one column per variable.
+    ///
+    /// NaN values are left empty.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("timestamp");
+        for name in &self.variables {
+            csv.push(',');
+            csv.push_str(name);
+        }
+        csv.push('\n');
+        for (i, timestamp) in self.timestamps.iter().enumerate() {
+            csv.push_str(&timestamp.to_string());
+            for values in &self.values {
+                csv.push(',');
+                if !values[i].is_nan() {
+                    csv.push_str(&values[i].to_string());
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Export the time series as a JSON object, such as:
+    /// `{"lon":2.5,"lat":48,"timestamps":[...],"values":{"wind":[...]}}`
+    ///
+    /// NaN values are written as `null`.
+    pub fn to_json(&self) -> String {
+        let timestamps: Vec<String> = self.timestamps.iter().map(|t| t.to_string()).collect();
+        let values: Vec<String> = self
+            .variables
+            .iter()
+            .zip(self.values.iter())
+            .map(|(name, values)| format!("{}:{}", json::string(name), json::numbers(values)))
+            .collect();
+        format!(
+            "{{\"lon\":{},\"lat\":{},\"timestamps\":[{}],\"values\":{{{}}}}}",
+            json::number(self.lon),
+            json::number(self.lat),
+            timestamps.join(","),
+            values.join(",")
+        )
+    }
+}
+
+/// Returns the number of days between 1970-01-01 and the given date
+/// (proleptic gregorian calendar).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_shifted = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_shifted + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Parses CF time units, such as "hours since 2017-08-01 00:00:00".
+///
+/// Returns the duration of a time step in seconds and
+/// the reference date as a Unix timestamp.
+pub fn parse_time_units(units: &str) -> Option<(f64, i64)> {
+    let units_regex = Regex::new(
+        r"^\s*(?P<unit>\w+)\s+since\s+(?P<year>-?\d{1,4})-(?P<month>\d{1,2})-(?P<day>\d{1,2})([T\s]+(?P<hour>\d{1,2}):(?P<minute>\d{1,2})(:(?P<second>\d{1,2})(\.\d*)?)?)?\s*Z?\s*$"
+    ).unwrap();
+    let capture = units_regex.captures(units)?;
+    let step: f64 = match capture.name("unit")?.as_str().to_lowercase().as_str() {
+        "seconds" | "second" | "secs" | "sec" | "s" => 1.,
+        "minutes" | "minute" | "mins" | "min" => 60.,
+        "hours" | "hour" | "hrs" | "hr" | "h" => 3600.,
+        "days" | "day" | "d" => 86400.,
+        _ => return None,
+    };
+    // missing time fields default to 0
+    let field = |name: &str| -> i64 {
+        capture
+            .name(name)
+            .and_then(|m| m.as_str().parse::<i64>().ok())
+            .unwrap_or(0)
+    };
+    let days = days_from_civil(field("year"), field("month"), field("day"));
+    let reference = days * 86400 + field("hour") * 3600 + field("minute") * 60 + field("second");
+    Some((step, reference))
+}
+
+#[test]
+fn test_parse_time_units() {
+    assert_eq!(parse_time_units("hours since 1970-01-01 00:00:00"), Some((3600., 0)));
+    assert_eq!(parse_time_units("days since 2017-08-01"), Some((86400., 1_501_545_600)));
+    assert_eq!(
+        parse_time_units("seconds since 2017-08-01T03:00:00Z"),
+        Some((1., 1_501_556_400))
+    );
+    assert_eq!(parse_time_units("fortnights since 2017-08-01"), None);
+    assert_eq!(parse_time_units("m s-1"), None);
+}
+
+#[test]
+fn test_time_series_export() {
+    let series = TimeSeries {
+        lon: 2.5,
+        lat: 48.,
+        timestamps: vec![0, 3600],
+        variables: vec!["u".into(), "v".into()],
+        values: vec![vec![1.5, ::std::f32::NAN], vec![2., 3.]],
+    };
+    assert_eq!(series.to_csv(), "timestamp,u,v\n0,1.5,2\n3600,,3\n");
+    assert_eq!(
+        series.to_json(),
+        "{\"lon\":2.5,\"lat\":48,\"timestamps\":[0,3600],\"values\":{\"u\":[1.5,null],\"v\":[2,3]}}"
+    );
+    assert_eq!(series.pairs("v"), Some(vec![(0, 2.), (3600, 3.)]));
+    assert_eq!(series.pairs("w"), None);
+}
@@ -0,0 +1,67 @@
+use tile::haversine_distance;
+
+/// Holds values sampled along a path (polyline),
+/// optionally across the vertical dimension to form a cross-section.
+#[derive(Debug)]
+pub struct Transect {
+    /// Distance of each sample from the start of the path (meters)
+    pub distances: Vec<f64>,
+    /// Longitude of each sample (WGS 84)
+    pub lons: Vec<f64>,
+    /// Latitude of each sample (WGS 84)
+    pub lats: Vec<f64>,
+    /// Values of the vertical coordinate, empty if the transect is horizontal only
+    pub levels: Vec<f64>,
+    /// One vector of values per sample, holding one value per level
+    /// (or a single value if no vertical dimension was requested).
+    /// Samples outside the dataset are NaN.
+    pub values: Vec<Vec<f32>>,
+}
+
+/// Samples points every `spacing` meters along a path of (lon, lat) vertices (WGS 84).
+///
+/// Returns (distance, lon, lat) triplets, the first and last vertices are always included.
+pub fn sample_path(path: &[(f64, f64)], spacing: f64) -> Vec<(f64, f64, f64)> {
+    let mut samples: Vec<(f64, f64, f64)> = Vec::new();
+    if path.is_empty() || spacing.is_nan() || spacing <= 0. {
+        return samples;
+    }
+    samples.push((0., path[0].0, path[0].1));
+    // distance of the first vertex of the current segment
+    let mut segment_start: f64 = 0.;
+    // distance of the next sample
+    let mut next: f64 = spacing;
+    for segment in path.windows(2) {
+        let (lon_a, lat_a) = segment[0];
+        let (lon_b, lat_b) = segment[1];
+        let length = haversine_distance(lon_a, lat_a, lon_b, lat_b);
+        while next < segment_start + length {
+            // linearly interpolate the position inside the segment
+            let ratio = (next - segment_start) / length;
+            samples.push((
+                next,
+                lon_a + (lon_b - lon_a) * ratio,
+                lat_a + (lat_b - lat_a) * ratio,
+            ));
+            next += spacing;
+        }
+        segment_start += length;
+    }
+    if path.len() > 1 {
+        let (lon, lat) = path[path.len() - 1];
+        samples.push((segment_start, lon, lat));
+    }
+    samples
+}
+
+#[test]
+fn test_sample_path() {
+    // one degree of longitude along the equator
+    let degree = haversine_distance(0., 0., 1., 0.);
+    let samples = sample_path(&[(0., 0.), (1., 0.), (1., 1.)], degree / 2.);
+    assert_eq!(samples.len(), 5);
+    assert!((samples[1].1 - 0.5).abs() < 1e-9);
+    assert!((samples[2].0 - degree).abs() < 1e-6);
+    assert_eq!((samples[4].1, samples[4].2), (1., 1.));
+    assert!(sample_path(&[(0., 0.), (1., 0.)], 0.).is_empty());
+}
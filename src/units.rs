@@ -0,0 +1,114 @@
+/// Linear conversion between two units: `converted = value * factor + offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitConversion {
+    pub factor: f32,
+    pub offset: f32,
+}
+
+impl UnitConversion {
+    #[inline]
+    pub fn apply(&self, value: f32) -> f32 {
+        value * self.factor + self.offset
+    }
+}
+
+// Each table associates unit names (and their aliases) with
+// the (factor, offset) converting them into the first unit of the table.
+const TEMPERATURE: &[(&[&str], f64, f64)] = &[
+    (&["K", "kelvin", "degK", "deg_K"], 1., 0.),
+    (&["degC", "°C", "C", "celsius", "degree_Celsius", "degrees_Celsius", "deg_C"], 1., 273.15),
+    (&["degF", "°F", "F", "fahrenheit", "degree_Fahrenheit", "deg_F"], 5. / 9., 459.67 * 5. / 9.),
+];
+const SPEED: &[(&[&str], f64, f64)] = &[
+    (&["m s-1", "m/s", "ms-1", "meter second-1", "meters second-1"], 1., 0.),
+    (&["km h-1", "km/h", "kmh"], 1. / 3.6, 0.),
+    (&["kt", "kn", "knot", "knots"], 1852. / 3600., 0.),
+    (&["mph", "mi h-1", "mi/h"], 0.44704, 0.),
+    (&["cm s-1", "cm/s"], 0.01, 0.),
+];
+const PRECIPITATION_FLUX: &[(&[&str], f64, f64)] = &[
+    // 1 kg of water over 1 m2 is 1 mm deep
+    (&["kg m-2 s-1", "mm s-1", "mm/s"], 1., 0.),
+    (&["mm h-1", "mm/h", "mm hr-1"], 1. / 3600., 0.),
+    (&["mm day-1", "mm/day", "mm d-1"], 1. / 86400., 0.),
+];
+const ACCUMULATION: &[(&[&str], f64, f64)] = &[
+    (&["kg m-2", "mm"], 1., 0.),
+    (&["cm"], 10., 0.),
+    (&["in", "inch", "inches"], 25.4, 0.),
+];
+const LENGTH: &[(&[&str], f64, f64)] = &[
+    (&["m", "meter", "meters", "metre", "metres"], 1., 0.),
+    (&["km"], 1000., 0.),
+    (&["cm"], 0.01, 0.),
+    (&["mm"], 0.001, 0.),
+    (&["ft", "feet"], 0.3048, 0.),
+    (&["nmi", "nautical_mile"], 1852., 0.),
+    (&["mi", "mile", "miles"], 1609.344, 0.),
+];
+const PRESSURE: &[(&[&str], f64, f64)] = &[
+    (&["Pa", "pascal"], 1., 0.),
+    (&["hPa", "mbar", "mb", "millibar"], 100., 0.),
+    (&["kPa"], 1000., 0.),
+    (&["bar"], 100_000., 0.),
+    (&["atm"], 101_325., 0.),
+    (&["inHg"], 3386.389, 0.),
+];
+const RATIO: &[(&[&str], f64, f64)] = &[
+    (&["1", "fraction", "0-1"], 1., 0.),
+    (&["%", "percent"], 0.01, 0.),
+];
+const QUANTITIES: &[&[(&[&str], f64, f64)]] = &[
+    TEMPERATURE,
+    SPEED,
+    PRECIPITATION_FLUX,
+    ACCUMULATION,
+    LENGTH,
+    PRESSURE,
+    RATIO,
+];
+
+/// Normalizes the notation of a unit, so "m.s^-1", "m s**-1" and "m  s-1" are all "m s-1"
+fn normalize_unit(unit: &str) -> String {
+    let unit = unit.replace("**", "").replace('^', "").replace('.', " ");
+    unit.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Returns the conversion from the `from` unit to the `to` unit,
+/// or None if they are unknown or describe different quantities.
+pub fn conversion(from: &str, to: &str) -> Option<UnitConversion> {
+    let (from, to) = (normalize_unit(from), normalize_unit(to));
+    // find a quantity knowing both units
+    for quantity in QUANTITIES {
+        let find = |unit: &str| {
+            quantity
+                .iter()
+                .find(|entry| entry.0.contains(&unit))
+                .map(|entry| (entry.1, entry.2))
+        };
+        if let (Some((from_factor, from_offset)), Some((to_factor, to_offset))) =
+            (find(&from), find(&to))
+        {
+            return Some(UnitConversion {
+                factor: (from_factor / to_factor) as f32,
+                offset: ((from_offset - to_offset) / to_factor) as f32,
+            });
+        }
+    }
+    None
+}
+
+#[test]
+fn test_unit_conversions() {
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+    let kelvin_to_celsius = conversion("K", "degC").unwrap();
+    assert!(close(kelvin_to_celsius.apply(273.15), 0.));
+    let celsius_to_fahrenheit = conversion("°C", "degF").unwrap();
+    assert!(close(celsius_to_fahrenheit.apply(100.), 212.));
+    assert!(close(conversion("m s**-1", "knots").unwrap().apply(1852. / 3600.), 1.));
+    assert!(close(conversion("kg m-2 s-1", "mm/h").unwrap().apply(1.), 3600.));
+    assert!(close(conversion("Pa", "hPa").unwrap().apply(101_325.), 1013.25));
+    assert!(close(conversion("m", "mm").unwrap().apply(1.), 1000.));
+    assert_eq!(conversion("K", "knots"), None);
+    assert_eq!(conversion("furlong", "m"), None);
+}
@@ -151,6 +151,25 @@ pub fn search_closest_idx_over(values: &[f64], target_value: f64) -> Option<usiz
     return None;
 }
 
+/// Infers the edges of the cells centered on **sorted** coordinates,
+/// as the midpoints between consecutive coordinates.
+/// The outer edges are extrapolated from the first and last cells.
+///
+/// Returns `values.len() + 1` edges (or an empty vector if `values` is empty).
+pub fn cell_edges(values: &[f64]) -> Vec<f64> {
+    if values.len() < 2 {
+        return values.iter().flat_map(|v| vec![*v, *v]).collect();
+    }
+    let n = values.len();
+    let mut edges: Vec<f64> = Vec::with_capacity(n + 1);
+    edges.push(values[0] - (values[1] - values[0]) / 2.);
+    for i in 1..n {
+        edges.push((values[i - 1] + values[i]) / 2.);
+    }
+    edges.push(values[n - 1] + (values[n - 1] - values[n - 2]) / 2.);
+    edges
+}
+
 #[test]
 fn test_binary_search() {
     let asc_values: Vec<f64> = vec![1., 3., 3.5, 5., 5.1, 6., 8., 11.];
@@ -186,3 +205,10 @@ fn test_binary_search_over() {
     assert_eq!(search_closest_idx_over(&desc_values, 850.), Some(0));
     assert_eq!(search_closest_idx_over(&desc_values, 1100.), Some(0));
 }
+
+#[test]
+fn test_cell_edges() {
+    assert_eq!(cell_edges(&[0., 1., 3.]), vec![-0.5, 0.5, 2., 4.]);
+    assert_eq!(cell_edges(&[2., 1.]), vec![2.5, 1.5, 0.5]);
+    assert_eq!(cell_edges(&[]), Vec::<f64>::new());
+}